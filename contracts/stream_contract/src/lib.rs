@@ -1,8 +1,8 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contracterror, contractimpl, contracttype, panic_with_error, token, Address, Env,
-    Symbol,
+    contract, contracterror, contractimpl, contracttype, token, Address, BytesN, Env, String,
+    Symbol, Vec,
 };
 
 #[contracttype]
@@ -11,6 +11,44 @@ pub enum DataKey {
     Stream(u64),
     StreamCounter,
     ProtocolConfig,
+    EmergencyStop,
+    AllowlistEnabled,
+    AllowedToken(Address),
+    TokenFeeRate(Address),
+    FeeDiscount(Address),
+    PendingFeeConfig,
+    FeeConfigDelay,
+    /// A shorter fee config delay waiting for the current delay to pass.
+    PendingFeeConfigDelay,
+    AccruedFees(Address),
+    ReferralShare,
+    /// Fees owed to a referrer, keyed by (referrer, token).
+    ReferralFees(Address, Address),
+    TreasurySplits,
+    PendingTreasurySplits,
+    /// A per-token fee override change waiting out the fee config delay.
+    PendingTokenFeeRate(Address),
+    MinFee(Address),
+    /// Tokens the contract owes per token: unstreamed deposits, rent budgets and unclaimed fees.
+    TotalLocked(Address),
+    /// The `n`th stream funded by an address, in creation order.
+    SenderStream(Address, u32),
+    /// How many streams an address has funded.
+    SenderStreamCount(Address),
+    /// The `n`th stream paying out to an address, in creation order.
+    RecipientStream(Address, u32),
+    /// How many streams pay out to an address.
+    RecipientStreamCount(Address),
+    /// Running volume and stream counts per token.
+    ProtocolStats(Address),
+    /// Runway (in seconds) below which streams report `StreamRunwayLow`.
+    RunwayThreshold,
+    /// Operators a recipient has approved to withdraw on its behalf.
+    OperatorApprovals(Address),
+    /// Sender-funded tip budget paying keepers that call `push_withdraw` on a stream.
+    PushTip(u64),
+    /// Set once `poke` has reported a stream as depleted; cleared when it is topped up.
+    DepletionNotified(u64),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -25,6 +63,70 @@ pub struct Stream {
     pub start_time: u64,
     pub last_update_time: u64,
     pub is_active: bool,
+    /// Sender-funded budget (in `token_address`) paid out to keepers that bump this stream's TTL.
+    pub rent_balance: i128,
+    pub last_bump_time: u64,
+    /// Ledger at which the stream entry's TTL was last extended to `STREAM_BUMP_AMOUNT`.
+    pub last_bump_ledger: u32,
+    /// Fee rate (bps) in effect when the stream was created; charged on its top-ups.
+    pub fee_rate_bps: u32,
+    /// `fee_rate_bps` after the sender's fee discount at creation. Discounts granted later can
+    /// still lower the charged rate, but revoking one never raises it above this.
+    pub discounted_fee_rate_bps: u32,
+    pub fee_mode: FeeMode,
+    /// Fee rounding and per-operation minimum fee in effect when the stream was created.
+    pub fee_rounding: FeeRounding,
+    pub min_fee: i128,
+    /// Integrator that receives a share of this stream's fees.
+    pub referrer: Option<Address>,
+    pub metadata: StreamMetadata,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PushTip {
+    /// Tokens left to pay keepers, refunded to the sender when the stream ends.
+    pub balance: i128,
+    /// Most a keeper is paid for one `push_withdraw`.
+    pub per_call: i128,
+}
+
+/// Which of a recipient's streams an operator may withdraw from.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OperatorScope {
+    Stream(u64),
+    All,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OperatorApproval {
+    pub operator: Address,
+    pub scope: OperatorScope,
+}
+
+/// Optional settings for `create_stream`; `Default` gives none of them.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CreateStreamOptions {
+    /// Extra deposit that pays keepers calling `bump_stream`; whatever is left is refunded
+    /// to the sender when the stream ends.
+    pub rent_budget: i128,
+    /// Integrator credited with a share of this stream's fees.
+    pub referrer: Option<Address>,
+    pub metadata: StreamMetadata,
+}
+
+/// Descriptive data for reconciling a stream with off-chain records such as invoices.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StreamMetadata {
+    /// Free-form note, at most `MAX_MEMO_LEN` bytes.
+    pub memo: Option<String>,
+    pub category: Option<Symbol>,
+    /// Hash of an external document, e.g. an invoice or payroll record.
+    pub reference: Option<BytesN<32>>,
 }
 
 #[contracttype]
@@ -33,6 +135,105 @@ pub struct ProtocolConfig {
     pub admin: Address,
     pub treasury: Address,
     pub fee_rate_bps: u32,
+    /// Fee mode given to newly created streams.
+    pub fee_mode: FeeMode,
+    pub fee_rounding: FeeRounding,
+    /// Smallest `amount` accepted by `create_stream` (0 for no minimum).
+    pub min_amount: i128,
+    /// Bounds on `create_stream`'s `duration` in seconds (0 for no bound).
+    pub min_duration: u64,
+    pub max_duration: u64,
+}
+
+/// How fractional fees from `amount * fee_rate_bps / 10_000` are rounded.
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FeeRounding {
+    Floor,
+    Ceil,
+}
+
+/// When the protocol fee is taken from a stream.
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FeeMode {
+    /// Deducted from deposits in `create_stream` and `top_up_stream`.
+    OnDeposit,
+    /// Deducted from each payout in `withdraw`, so refunded funds are never charged.
+    OnWithdraw,
+}
+
+/// A fee config change waiting out the timelock. Also the payload of the
+/// `fee_config_queued`, `fee_config_executed` and `fee_config_cancelled` events.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingFeeConfig {
+    pub treasury: Address,
+    pub fee_rate_bps: u32,
+    /// Earliest ledger timestamp at which the change can be executed.
+    pub eta: u64,
+}
+
+/// A treasury split change waiting out the fee config delay.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingTreasurySplits {
+    pub splits: Vec<TreasurySplit>,
+    /// Earliest ledger timestamp at which the change can be executed.
+    pub eta: u64,
+}
+
+/// A per-token fee override change waiting out the fee config delay.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingTokenFeeRate {
+    pub token: Address,
+    /// New override, or `None` to remove it and fall back to the global rate.
+    pub fee_rate_bps: Option<u32>,
+    /// Earliest ledger timestamp at which the change can be executed.
+    pub eta: u64,
+}
+
+/// A reduction of the fee config delay, applied once the delay it replaces has passed.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingFeeConfigDelay {
+    pub delay: u64,
+    /// Ledger timestamp from which `delay` is in effect.
+    pub eta: u64,
+}
+
+/// One destination of the protocol's fee revenue.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TreasurySplit {
+    pub destination: Address,
+    /// Share of accrued fees in basis points; all splits add up to 10_000.
+    pub weight_bps: u32,
+}
+
+/// Aggregate activity for one token, maintained as streams are funded and paid out.
+#[contracttype]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProtocolStats {
+    /// Principal received by the contract across creates and top-ups, before fees.
+    pub total_deposited: i128,
+    /// Tokens paid out to recipients.
+    pub total_withdrawn: i128,
+    /// Fees charged, including any referrer share.
+    pub total_fees: i128,
+    pub active_streams: u64,
+    pub total_streams: u64,
+}
+
+/// Fee treatment for a specific stream sender.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FeeDiscount {
+    /// No protocol fee is charged.
+    Exempt,
+    /// Fee rate in basis points charged instead of the normal rate, if lower.
+    ReducedRate(u32),
 }
 
 #[contracterror]
@@ -46,6 +247,38 @@ pub enum StreamError {
     NotAdmin = 6,
     InvalidFeeRate = 7,
     NotInitialized = 8,
+    EmergencyStopEnabled = 9,
+    BumpTooSoon = 10,
+    TokenNotAllowed = 11,
+    FeeConfigTimelocked = 12,
+    NoPendingFeeConfig = 13,
+    TimelockNotExpired = 14,
+    InvalidDelay = 15,
+    InvalidReferrer = 16,
+    InvalidShare = 17,
+    TreasurySplitsActive = 18,
+    NoTreasurySplits = 19,
+    InvalidDuration = 20,
+    SelfStream = 21,
+    /// `net_amount / duration` rounds down to zero, so the stream would never pay out.
+    RateTooLow = 22,
+    ArithmeticOverflow = 23,
+    /// The recipient is the stream contract itself.
+    InvalidRecipient = 24,
+    AmountTooLow = 25,
+    DurationTooShort = 26,
+    DurationTooLong = 27,
+    /// The contract's token balance is below what it owes.
+    InvariantViolated = 28,
+    /// The memo is longer than `MAX_MEMO_LEN` bytes.
+    MemoTooLong = 29,
+    OperatorNotApproved = 30,
+    /// The recipient already has `MAX_OPERATOR_APPROVALS` approvals.
+    TooManyOperators = 31,
+    /// `push_withdraw` was called before `MIN_PUSH_INTERVAL_SECS` of accrual.
+    PushTooSoon = 32,
+    NoPendingTreasurySplits = 33,
+    NoPendingTokenFeeRate = 34,
 }
 
 #[contracttype]
@@ -57,6 +290,7 @@ pub struct StreamCreatedEvent {
     pub rate: i128,
     pub token_address: Address,
     pub start_time: u64,
+    pub metadata: StreamMetadata,
 }
 
 #[contracttype]
@@ -90,9 +324,277 @@ pub struct StreamToppedUpEvent {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct FeeCollectedEvent {
     pub stream_id: u64,
-    pub treasury: Address,
+    /// Where the treasury's part of the fee is routed: the treasury splits if any are set,
+    /// otherwise the treasury alone at 10_000 bps.
+    pub recipients: Vec<TreasurySplit>,
     pub fee_amount: i128,
     pub token: Address,
+    /// Basis points knocked off the normal rate by the sender's fee discount (0 if none).
+    pub discount_bps: u32,
+    pub fee_mode: FeeMode,
+    pub referrer: Option<Address>,
+    /// Portion of `fee_amount` credited to the referrer rather than the treasury.
+    pub referrer_amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamBumpedEvent {
+    pub stream_id: u64,
+    pub keeper: Address,
+    pub reward: i128,
+    pub rent_balance: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamFeeRateUpdatedEvent {
+    pub stream_id: u64,
+    pub old_fee_rate_bps: u32,
+    pub new_fee_rate_bps: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeesClaimedEvent {
+    pub token: Address,
+    pub treasury: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReferralFeesClaimedEvent {
+    pub referrer: Address,
+    pub token: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokensSweptEvent {
+    pub token: Address,
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamMetadataUpdatedEvent {
+    pub stream_id: u64,
+    pub old_metadata: StreamMetadata,
+    pub new_metadata: StreamMetadata,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PushTipFundedEvent {
+    pub stream_id: u64,
+    pub amount: i128,
+    pub per_call: i128,
+    pub balance: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PushWithdrawnEvent {
+    pub stream_id: u64,
+    pub keeper: Address,
+    /// Amount sent to the recipient.
+    pub amount: i128,
+    pub tip: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamLimitsUpdatedEvent {
+    pub min_amount: i128,
+    pub min_duration: u64,
+    pub max_duration: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenFeeRateUpdatedEvent {
+    pub token: Address,
+    pub fee_rate_bps: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeDiscountUpdatedEvent {
+    pub account: Address,
+    pub discount: FeeDiscount,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TokenAllowedUpdatedEvent {
+    pub token: Address,
+    pub allowed: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MinFeeUpdatedEvent {
+    pub token: Address,
+    /// New minimum; 0 means the minimum was removed.
+    pub min_fee: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OperatorApprovalEvent {
+    pub recipient: Address,
+    pub operator: Address,
+    pub scope: OperatorScope,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamRunwayLowEvent {
+    pub stream_id: u64,
+    /// Deposit not yet streamed to the recipient.
+    pub remaining: i128,
+    /// Seconds until the stream runs dry at its current rate.
+    pub runway_secs: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamDepletedEvent {
+    pub stream_id: u64,
+    /// Accrued amount the recipient has yet to withdraw.
+    pub unclaimed: i128,
+    /// When accrual reached the full deposit.
+    pub depleted_at: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InitializedEvent {
+    pub admin: Address,
+    pub treasury: Address,
+    pub fee_rate_bps: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeConfigUpdatedEvent {
+    pub treasury: Address,
+    pub old_fee_rate_bps: u32,
+    pub new_fee_rate_bps: u32,
+}
+
+/// Every event the contract publishes.
+///
+/// Stream events are published under the topics
+/// `("stream", name, stream_id, sender, recipient)` so indexers can filter by either party;
+/// protocol-level events use `("stream", name)`. The payload is always an `EventEnvelope`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ContractEvent {
+    Initialized(InitializedEvent),
+    FeeConfigUpdated(FeeConfigUpdatedEvent),
+    FeeConfigQueued(PendingFeeConfig),
+    FeeConfigExecuted(PendingFeeConfig),
+    FeeConfigCancelled(PendingFeeConfig),
+    FeeConfigDelayUpdated(u64),
+    FeeConfigDelayQueued(PendingFeeConfigDelay),
+    TreasurySplitsUpdated(Vec<TreasurySplit>),
+    TreasurySplitsQueued(PendingTreasurySplits),
+    TreasurySplitsCancelled(PendingTreasurySplits),
+    FeesClaimed(FeesClaimedEvent),
+    ReferralFeesClaimed(ReferralFeesClaimedEvent),
+    TokensSwept(TokensSweptEvent),
+    StreamCreated(StreamCreatedEvent),
+    StreamCancelled(StreamCancelledEvent),
+    TokensWithdrawn(TokensWithdrawnEvent),
+    StreamToppedUp(StreamToppedUpEvent),
+    StreamBumped(StreamBumpedEvent),
+    StreamFeeRateUpdated(StreamFeeRateUpdatedEvent),
+    FeeCollected(FeeCollectedEvent),
+    RunwayThresholdUpdated(u64),
+    StreamRunwayLow(StreamRunwayLowEvent),
+    StreamDepleted(StreamDepletedEvent),
+    StreamMetadataUpdated(StreamMetadataUpdatedEvent),
+    OperatorApproved(OperatorApprovalEvent),
+    OperatorRevoked(OperatorApprovalEvent),
+    PushTipFunded(PushTipFundedEvent),
+    PushWithdrawn(PushWithdrawnEvent),
+    FeeModeUpdated(FeeMode),
+    FeeRoundingUpdated(FeeRounding),
+    StreamLimitsUpdated(StreamLimitsUpdatedEvent),
+    TokenFeeRateUpdated(TokenFeeRateUpdatedEvent),
+    TokenFeeRateRemoved(Address),
+    TokenFeeRateQueued(PendingTokenFeeRate),
+    TokenFeeRateCancelled(PendingTokenFeeRate),
+    FeeDiscountUpdated(FeeDiscountUpdatedEvent),
+    FeeDiscountRemoved(Address),
+    AllowlistEnabledUpdated(bool),
+    TokenAllowedUpdated(TokenAllowedUpdatedEvent),
+    EmergencyModeUpdated(bool),
+    ReferralShareUpdated(u32),
+    MinFeeUpdated(MinFeeUpdatedEvent),
+}
+
+impl ContractEvent {
+    /// Snake-case name published as the second topic.
+    fn name(&self) -> &'static str {
+        match self {
+            ContractEvent::Initialized(_) => "initialized",
+            ContractEvent::FeeConfigUpdated(_) => "fee_config_updated",
+            ContractEvent::FeeConfigQueued(_) => "fee_config_queued",
+            ContractEvent::FeeConfigExecuted(_) => "fee_config_executed",
+            ContractEvent::FeeConfigCancelled(_) => "fee_config_cancelled",
+            ContractEvent::FeeConfigDelayUpdated(_) => "fee_config_delay_updated",
+            ContractEvent::FeeConfigDelayQueued(_) => "fee_config_delay_queued",
+            ContractEvent::TreasurySplitsUpdated(_) => "treasury_splits_updated",
+            ContractEvent::TreasurySplitsQueued(_) => "treasury_splits_queued",
+            ContractEvent::TreasurySplitsCancelled(_) => "treasury_splits_cancelled",
+            ContractEvent::FeesClaimed(_) => "fees_claimed",
+            ContractEvent::ReferralFeesClaimed(_) => "referral_fees_claimed",
+            ContractEvent::TokensSwept(_) => "tokens_swept",
+            ContractEvent::StreamCreated(_) => "stream_created",
+            ContractEvent::StreamCancelled(_) => "stream_cancelled",
+            ContractEvent::TokensWithdrawn(_) => "tokens_withdrawn",
+            ContractEvent::StreamToppedUp(_) => "stream_topped_up",
+            ContractEvent::StreamBumped(_) => "stream_bumped",
+            ContractEvent::StreamFeeRateUpdated(_) => "stream_fee_rate_updated",
+            ContractEvent::FeeCollected(_) => "fee_collected",
+            ContractEvent::RunwayThresholdUpdated(_) => "runway_threshold_updated",
+            ContractEvent::StreamRunwayLow(_) => "stream_runway_low",
+            ContractEvent::StreamDepleted(_) => "stream_depleted",
+            ContractEvent::StreamMetadataUpdated(_) => "stream_metadata_updated",
+            ContractEvent::OperatorApproved(_) => "operator_approved",
+            ContractEvent::OperatorRevoked(_) => "operator_revoked",
+            ContractEvent::PushTipFunded(_) => "push_tip_funded",
+            ContractEvent::PushWithdrawn(_) => "push_withdrawn",
+            ContractEvent::FeeModeUpdated(_) => "fee_mode_updated",
+            ContractEvent::FeeRoundingUpdated(_) => "fee_rounding_updated",
+            ContractEvent::StreamLimitsUpdated(_) => "stream_limits_updated",
+            ContractEvent::TokenFeeRateUpdated(_) => "token_fee_rate_updated",
+            ContractEvent::TokenFeeRateRemoved(_) => "token_fee_rate_removed",
+            ContractEvent::TokenFeeRateQueued(_) => "token_fee_rate_queued",
+            ContractEvent::TokenFeeRateCancelled(_) => "token_fee_rate_cancelled",
+            ContractEvent::FeeDiscountUpdated(_) => "fee_discount_updated",
+            ContractEvent::FeeDiscountRemoved(_) => "fee_discount_removed",
+            ContractEvent::AllowlistEnabledUpdated(_) => "allowlist_enabled_updated",
+            ContractEvent::TokenAllowedUpdated(_) => "token_allowed_updated",
+            ContractEvent::EmergencyModeUpdated(_) => "emergency_mode_updated",
+            ContractEvent::ReferralShareUpdated(_) => "referral_share_updated",
+            ContractEvent::MinFeeUpdated(_) => "min_fee_updated",
+        }
+    }
+}
+
+/// Payload of every published event.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EventEnvelope {
+    /// `EVENT_SCHEMA_VERSION` at the time the event was published.
+    pub version: u32,
+    pub event: ContractEvent,
 }
 
 #[contract]
@@ -101,6 +603,63 @@ pub struct StreamContract;
 /// Maximum fee rate: 1000 basis points = 10%
 const MAX_FEE_RATE_BPS: u32 = 1_000;
 
+/// Default wait between queueing and executing a fee config change.
+const DEFAULT_FEE_CONFIG_DELAY: u64 = 2 * 24 * 60 * 60;
+const MIN_FEE_CONFIG_DELAY: u64 = 24 * 60 * 60;
+const MAX_FEE_CONFIG_DELAY: u64 = 30 * 24 * 60 * 60;
+
+/// Upper bound on treasury destinations, keeping `distribute_fees` within budget.
+const MAX_TREASURY_SPLITS: u32 = 10;
+
+const DAY_IN_LEDGERS: u32 = 17_280;
+/// A bumped stream entry lives for 30 days; it is only re-extended once it drops below 23 days.
+const STREAM_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+const STREAM_LIFETIME_THRESHOLD: u32 = STREAM_BUMP_AMOUNT - 7 * DAY_IN_LEDGERS;
+/// Keepers are paid at most once per this many seconds for a given stream.
+const BUMP_INTERVAL_SECS: u64 = 7 * 24 * 60 * 60;
+/// Ledgers after an extension before the entry's TTL is back down to the threshold,
+/// i.e. before another bump actually extends anything.
+const BUMP_INTERVAL_LEDGERS: u32 = STREAM_BUMP_AMOUNT - STREAM_LIFETIME_THRESHOLD;
+/// Accrual a stream must build up before anyone can push it to the recipient,
+/// so keepers can't farm tips with dust withdrawals.
+const MIN_PUSH_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// Longest memo, in bytes, that can be attached to a stream.
+const MAX_MEMO_LEN: u32 = 256;
+
+/// Upper bound on operator approvals per recipient, keeping the approval check cheap.
+const MAX_OPERATOR_APPROVALS: u32 = 20;
+
+/// Default runway below which withdrawals and top-ups report `StreamRunwayLow`.
+const DEFAULT_RUNWAY_THRESHOLD: u64 = 7 * 24 * 60 * 60;
+
+/// Upper bound on the number of IDs returned by one page of an index query.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// First topic of every event this contract publishes.
+const EVENT_NAMESPACE: &str = "stream";
+/// Bumped whenever a topic layout or payload shape changes.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+// ─── Checked Arithmetic ──────────────────────────────────────────────
+
+fn checked_add(a: i128, b: i128) -> Result<i128, StreamError> {
+    a.checked_add(b).ok_or(StreamError::ArithmeticOverflow)
+}
+
+fn checked_sub(a: i128, b: i128) -> Result<i128, StreamError> {
+    a.checked_sub(b).ok_or(StreamError::ArithmeticOverflow)
+}
+
+fn checked_mul(a: i128, b: i128) -> Result<i128, StreamError> {
+    a.checked_mul(b).ok_or(StreamError::ArithmeticOverflow)
+}
+
+/// `amount * bps / 10_000`, rounded down.
+fn bps_of(amount: i128, bps: u32) -> Result<i128, StreamError> {
+    Ok(checked_mul(amount, bps as i128)? / 10_000)
+}
+
 #[contractimpl]
 impl StreamContract {
     // ─── Admin: Protocol Fee Configuration ───────────────────────────
@@ -128,182 +687,1333 @@ impl StreamContract {
         }
 
         let config = ProtocolConfig {
-            admin,
-            treasury,
+            admin: admin.clone(),
+            treasury: treasury.clone(),
             fee_rate_bps,
+            fee_mode: FeeMode::OnDeposit,
+            fee_rounding: FeeRounding::Floor,
+            min_amount: 0,
+            min_duration: 0,
+            max_duration: 0,
         };
         env.storage()
             .instance()
             .set(&DataKey::ProtocolConfig, &config);
 
+        Self::publish_event(
+            &env,
+            ContractEvent::Initialized(InitializedEvent {
+                admin,
+                treasury,
+                fee_rate_bps,
+            }),
+        );
+
         Ok(())
     }
 
-    /// Update the treasury address and/or fee rate. Admin-only.
+    /// Update the fee config immediately. Admin-only.
+    /// Only usable for changes that cannot hurt users (same treasury, same or lower rate);
+    /// anything else must go through `queue_fee_config`.
     pub fn update_fee_config(
         env: Env,
         admin: Address,
         treasury: Address,
         fee_rate_bps: u32,
     ) -> Result<(), StreamError> {
-        admin.require_auth();
-
-        let config: ProtocolConfig = env
-            .storage()
-            .instance()
-            .get(&DataKey::ProtocolConfig)
-            .ok_or(StreamError::NotInitialized)?;
-
-        if config.admin != admin {
-            return Err(StreamError::NotAdmin);
-        }
+        let config = Self::require_admin(&env, &admin)?;
 
         if fee_rate_bps > MAX_FEE_RATE_BPS {
             return Err(StreamError::InvalidFeeRate);
         }
 
+        if fee_rate_bps > config.fee_rate_bps || treasury != config.treasury {
+            return Err(StreamError::FeeConfigTimelocked);
+        }
+
+        let old_fee_rate_bps = config.fee_rate_bps;
         let new_config = ProtocolConfig {
-            admin: config.admin,
-            treasury,
+            treasury: treasury.clone(),
             fee_rate_bps,
+            ..config
         };
         env.storage()
             .instance()
             .set(&DataKey::ProtocolConfig, &new_config);
 
+        Self::publish_event(
+            &env,
+            ContractEvent::FeeConfigUpdated(FeeConfigUpdatedEvent {
+                treasury,
+                old_fee_rate_bps,
+                new_fee_rate_bps: fee_rate_bps,
+            }),
+        );
+
         Ok(())
     }
 
-    /// Read the current protocol fee configuration (returns None if not initialized).
-    pub fn get_fee_config(env: Env) -> Option<ProtocolConfig> {
+    /// Schedule a new treasury and fee rate, executable once the fee config delay has passed.
+    /// Replaces any change already pending. Admin-only.
+    pub fn queue_fee_config(
+        env: Env,
+        admin: Address,
+        treasury: Address,
+        fee_rate_bps: u32,
+    ) -> Result<PendingFeeConfig, StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if fee_rate_bps > MAX_FEE_RATE_BPS {
+            return Err(StreamError::InvalidFeeRate);
+        }
+
+        let eta = env
+            .ledger()
+            .timestamp()
+            .saturating_add(Self::get_fee_config_delay(env.clone()));
+        let pending = PendingFeeConfig {
+            treasury,
+            fee_rate_bps,
+            eta,
+        };
         env.storage()
             .instance()
-            .get(&DataKey::ProtocolConfig)
+            .set(&DataKey::PendingFeeConfig, &pending);
+
+        Self::publish_event(&env, ContractEvent::FeeConfigQueued(pending.clone()));
+
+        Ok(pending)
     }
 
-    // ─── Fee Collection ──────────────────────────────────────────────
+    /// Apply the pending fee config once its `eta` has been reached. Admin-only.
+    pub fn execute_fee_config(env: Env, admin: Address) -> Result<(), StreamError> {
+        let config = Self::require_admin(&env, &admin)?;
 
-    /// Deducts protocol fee from `amount` and transfers it to the treasury.
-    /// Returns the net amount (amount - fee). If no config or fee is 0, returns `amount` unchanged.
-    fn collect_fee(
-        env: &Env,
-        token_address: &Address,
-        amount: i128,
-        stream_id: u64,
-    ) -> i128 {
-        let config: Option<ProtocolConfig> = env
+        let pending: PendingFeeConfig = env
             .storage()
             .instance()
-            .get(&DataKey::ProtocolConfig);
+            .get(&DataKey::PendingFeeConfig)
+            .ok_or(StreamError::NoPendingFeeConfig)?;
 
-        match config {
-            Some(cfg) if cfg.fee_rate_bps > 0 => {
-                let fee = amount * (cfg.fee_rate_bps as i128) / 10_000;
-                if fee > 0 {
-                    let token_client = token::Client::new(env, token_address);
-                    let contract_address = env.current_contract_address();
-                    token_client.transfer(&contract_address, &cfg.treasury, &fee);
-
-                    env.events().publish(
-                        (Symbol::new(env, "fee_collected"), stream_id),
-                        FeeCollectedEvent {
-                            stream_id,
-                            treasury: cfg.treasury,
-                            fee_amount: fee,
-                            token: token_address.clone(),
-                        },
-                    );
-                }
-                amount - fee
-            }
-            _ => amount,
+        if env.ledger().timestamp() < pending.eta {
+            return Err(StreamError::TimelockNotExpired);
         }
-    }
 
-    // ─── Stream Operations ───────────────────────────────────────────
+        let new_config = ProtocolConfig {
+            treasury: pending.treasury.clone(),
+            fee_rate_bps: pending.fee_rate_bps,
+            ..config
+        };
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolConfig, &new_config);
+        env.storage().instance().remove(&DataKey::PendingFeeConfig);
 
-    pub fn create_stream(
-        env: Env,
-        sender: Address,
-        recipient: Address,
-        token_address: Address,
-        amount: i128,
-        duration: u64,
-    ) -> u64 {
-        sender.require_auth();
+        Self::publish_event(&env, ContractEvent::FeeConfigExecuted(pending));
 
-        if Self::is_emergency_mode(env.clone()) {
-            panic_with_error!(&env, StreamError::EmergencyStopEnabled);
-        }
+        Ok(())
+    }
 
-        if amount <= 0 || duration == 0 {
-            panic_with_error!(&env, StreamError::InvalidAmount);
-        }
+    /// Drop the pending fee config change. Admin-only.
+    pub fn cancel_fee_config(env: Env, admin: Address) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
 
-        let stream_id = Self::get_next_stream_id(&env);
-        let start_time = env.ledger().timestamp();
+        let pending: PendingFeeConfig = env
+            .storage()
+            .instance()
+            .get(&DataKey::PendingFeeConfig)
+            .ok_or(StreamError::NoPendingFeeConfig)?;
+        env.storage().instance().remove(&DataKey::PendingFeeConfig);
 
-        // Transfer full amount from sender to contract
-        let token_client = token::Client::new(&env, &token_address);
-        let contract_address = env.current_contract_address();
-        token_client.transfer(&sender, &contract_address, &amount);
+        Self::publish_event(&env, ContractEvent::FeeConfigCancelled(pending));
 
-        // Deduct protocol fee (if configured) and get net amount for the stream
-        let net_amount = Self::collect_fee(&env, &token_address, amount, stream_id);
-        let rate_per_second = net_amount / (duration as i128);
+        Ok(())
+    }
+
+    pub fn get_pending_fee_config(env: Env) -> Option<PendingFeeConfig> {
+        env.storage()
+            .instance()
+            .get(&DataKey::PendingFeeConfig)
+    }
+
+    /// Set how long (in seconds) queued fee config changes must wait. Admin-only.
+    /// Only affects changes queued afterwards. Increases apply immediately; a shorter delay
+    /// only takes effect once the current delay has passed, so it can't be used to rush
+    /// through a change.
+    pub fn set_fee_config_delay(env: Env, admin: Address, delay: u64) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if !(MIN_FEE_CONFIG_DELAY..=MAX_FEE_CONFIG_DELAY).contains(&delay) {
+            return Err(StreamError::InvalidDelay);
+        }
+
+        let current = Self::get_fee_config_delay(env.clone());
+        let storage = env.storage().instance();
+        if delay >= current {
+            storage.set(&DataKey::FeeConfigDelay, &delay);
+            storage.remove(&DataKey::PendingFeeConfigDelay);
+            Self::publish_event(&env, ContractEvent::FeeConfigDelayUpdated(delay));
+        } else {
+            // Pin the delay in force today so the pending one can't shorten its own wait.
+            storage.set(&DataKey::FeeConfigDelay, &current);
+            let pending = PendingFeeConfigDelay {
+                delay,
+                eta: env.ledger().timestamp().saturating_add(current),
+            };
+            storage.set(&DataKey::PendingFeeConfigDelay, &pending);
+            Self::publish_event(&env, ContractEvent::FeeConfigDelayQueued(pending));
+        }
+
+        Ok(())
+    }
+
+    /// The delay currently in force, including a queued reduction whose `eta` has passed.
+    pub fn get_fee_config_delay(env: Env) -> u64 {
+        if let Some(pending) = Self::get_pending_fee_config_delay(env.clone()) {
+            if env.ledger().timestamp() >= pending.eta {
+                return pending.delay;
+            }
+        }
+        env.storage()
+            .instance()
+            .get(&DataKey::FeeConfigDelay)
+            .unwrap_or(DEFAULT_FEE_CONFIG_DELAY)
+    }
+
+    pub fn get_pending_fee_config_delay(env: Env) -> Option<PendingFeeConfigDelay> {
+        env.storage()
+            .instance()
+            .get(&DataKey::PendingFeeConfigDelay)
+    }
+
+    /// Choose whether new streams pay fees on deposit or on withdrawal. Admin-only.
+    /// Existing streams keep the mode they were created with, so no payout is charged twice.
+    pub fn set_fee_mode(env: Env, admin: Address, fee_mode: FeeMode) -> Result<(), StreamError> {
+        let mut config = Self::require_admin(&env, &admin)?;
+
+        config.fee_mode = fee_mode;
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolConfig, &config);
+
+        Self::publish_event(&env, ContractEvent::FeeModeUpdated(fee_mode));
+
+        Ok(())
+    }
+
+    /// Choose whether fractional fees round down or up. Admin-only.
+    /// Existing streams keep the rounding they were created with.
+    pub fn set_fee_rounding(
+        env: Env,
+        admin: Address,
+        fee_rounding: FeeRounding,
+    ) -> Result<(), StreamError> {
+        let mut config = Self::require_admin(&env, &admin)?;
+
+        config.fee_rounding = fee_rounding;
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolConfig, &config);
+
+        Self::publish_event(&env, ContractEvent::FeeRoundingUpdated(fee_rounding));
+
+        Ok(())
+    }
+
+    /// Set the minimum amount and duration bounds enforced by `create_stream`.
+    /// Pass 0 for any bound that should not apply. Admin-only.
+    pub fn set_stream_limits(
+        env: Env,
+        admin: Address,
+        min_amount: i128,
+        min_duration: u64,
+        max_duration: u64,
+    ) -> Result<(), StreamError> {
+        let mut config = Self::require_admin(&env, &admin)?;
+
+        if min_amount < 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        if max_duration != 0 && min_duration > max_duration {
+            return Err(StreamError::InvalidDuration);
+        }
+
+        config.min_amount = min_amount;
+        config.min_duration = min_duration;
+        config.max_duration = max_duration;
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolConfig, &config);
+
+        Self::publish_event(
+            &env,
+            ContractEvent::StreamLimitsUpdated(StreamLimitsUpdatedEvent {
+                min_amount,
+                min_duration,
+                max_duration,
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// Set the runway (in seconds) below which `withdraw` and `top_up_stream` emit
+    /// `StreamRunwayLow`. 0 disables the notification. Admin-only.
+    pub fn set_runway_threshold(
+        env: Env,
+        admin: Address,
+        threshold: u64,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        env.storage()
+            .instance()
+            .set(&DataKey::RunwayThreshold, &threshold);
+
+        Self::publish_event(&env, ContractEvent::RunwayThresholdUpdated(threshold));
+
+        Ok(())
+    }
+
+    pub fn get_runway_threshold(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::RunwayThreshold)
+            .unwrap_or(DEFAULT_RUNWAY_THRESHOLD)
+    }
+
+    /// Read the current protocol fee configuration (returns None if not initialized).
+    pub fn get_fee_config(env: Env) -> Option<ProtocolConfig> {
+        env.storage()
+            .instance()
+            .get(&DataKey::ProtocolConfig)
+    }
+
+    /// Enable or disable emergency mode. Admin-only.
+    /// While enabled, new streams and top-ups are rejected; withdrawals and
+    /// cancellations still work.
+    pub fn set_emergency_mode(
+        env: Env,
+        admin: Address,
+        enabled: bool,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        env.storage()
+            .instance()
+            .set(&DataKey::EmergencyStop, &enabled);
+
+        Self::publish_event(&env, ContractEvent::EmergencyModeUpdated(enabled));
+
+        Ok(())
+    }
+
+    pub fn is_emergency_mode(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::EmergencyStop)
+            .unwrap_or(false)
+    }
+
+    /// Checks `admin` auth and that it matches the configured protocol admin.
+    fn require_admin(env: &Env, admin: &Address) -> Result<ProtocolConfig, StreamError> {
+        admin.require_auth();
+
+        let config: ProtocolConfig = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolConfig)
+            .ok_or(StreamError::NotInitialized)?;
+
+        if config.admin != *admin {
+            return Err(StreamError::NotAdmin);
+        }
+
+        Ok(config)
+    }
+
+    /// Charge `fee_rate_bps` for `token` instead of the global rate, immediately. Admin-only.
+    /// Only usable when this doesn't raise the token's effective rate; increases must go
+    /// through `queue_token_fee_rate`.
+    pub fn set_token_fee_rate(
+        env: Env,
+        admin: Address,
+        token: Address,
+        fee_rate_bps: u32,
+    ) -> Result<(), StreamError> {
+        let config = Self::require_admin(&env, &admin)?;
+
+        if fee_rate_bps > MAX_FEE_RATE_BPS {
+            return Err(StreamError::InvalidFeeRate);
+        }
+
+        if fee_rate_bps > Self::resolve_fee_rate(&env, &config, &token) {
+            return Err(StreamError::FeeConfigTimelocked);
+        }
+
+        Self::write_token_fee_rate(&env, token, Some(fee_rate_bps));
+
+        Ok(())
+    }
+
+    /// Drop the fee override for `token` so it falls back to the global rate, immediately.
+    /// Admin-only. Only usable when the global rate is no higher than the override; otherwise
+    /// the removal must go through `queue_token_fee_rate`.
+    pub fn remove_token_fee_rate(
+        env: Env,
+        admin: Address,
+        token: Address,
+    ) -> Result<(), StreamError> {
+        let config = Self::require_admin(&env, &admin)?;
+
+        if config.fee_rate_bps > Self::resolve_fee_rate(&env, &config, &token) {
+            return Err(StreamError::FeeConfigTimelocked);
+        }
+
+        Self::write_token_fee_rate(&env, token, None);
+
+        Ok(())
+    }
+
+    /// Schedule a change to `token`'s fee override (`None` removes it), executable once the
+    /// fee config delay has passed. Replaces any change already pending for `token`.
+    /// Admin-only.
+    pub fn queue_token_fee_rate(
+        env: Env,
+        admin: Address,
+        token: Address,
+        fee_rate_bps: Option<u32>,
+    ) -> Result<PendingTokenFeeRate, StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if fee_rate_bps.is_some_and(|rate| rate > MAX_FEE_RATE_BPS) {
+            return Err(StreamError::InvalidFeeRate);
+        }
+
+        let eta = env
+            .ledger()
+            .timestamp()
+            .saturating_add(Self::get_fee_config_delay(env.clone()));
+        let pending = PendingTokenFeeRate {
+            token: token.clone(),
+            fee_rate_bps,
+            eta,
+        };
+        env.storage()
+            .persistent()
+            .set(&DataKey::PendingTokenFeeRate(token), &pending);
+
+        Self::publish_event(&env, ContractEvent::TokenFeeRateQueued(pending.clone()));
+
+        Ok(pending)
+    }
+
+    /// Apply the pending override change for `token` once its `eta` has been reached.
+    /// Admin-only.
+    pub fn execute_token_fee_rate(
+        env: Env,
+        admin: Address,
+        token: Address,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let pending = Self::get_pending_token_fee_rate(env.clone(), token.clone())
+            .ok_or(StreamError::NoPendingTokenFeeRate)?;
+
+        if env.ledger().timestamp() < pending.eta {
+            return Err(StreamError::TimelockNotExpired);
+        }
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PendingTokenFeeRate(token.clone()));
+        Self::write_token_fee_rate(&env, token, pending.fee_rate_bps);
+
+        Ok(())
+    }
+
+    /// Drop the pending override change for `token`. Admin-only.
+    pub fn cancel_token_fee_rate(
+        env: Env,
+        admin: Address,
+        token: Address,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let pending = Self::get_pending_token_fee_rate(env.clone(), token.clone())
+            .ok_or(StreamError::NoPendingTokenFeeRate)?;
+        env.storage()
+            .persistent()
+            .remove(&DataKey::PendingTokenFeeRate(token));
+
+        Self::publish_event(&env, ContractEvent::TokenFeeRateCancelled(pending));
+
+        Ok(())
+    }
+
+    pub fn get_pending_token_fee_rate(env: Env, token: Address) -> Option<PendingTokenFeeRate> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PendingTokenFeeRate(token))
+    }
+
+    /// Sets or removes `token`'s fee override and publishes the matching event.
+    fn write_token_fee_rate(env: &Env, token: Address, fee_rate_bps: Option<u32>) {
+        let key = DataKey::TokenFeeRate(token.clone());
+        match fee_rate_bps {
+            Some(fee_rate_bps) => {
+                env.storage().persistent().set(&key, &fee_rate_bps);
+                Self::publish_event(
+                    env,
+                    ContractEvent::TokenFeeRateUpdated(TokenFeeRateUpdatedEvent {
+                        token,
+                        fee_rate_bps,
+                    }),
+                );
+            }
+            None => {
+                env.storage().persistent().remove(&key);
+                Self::publish_event(env, ContractEvent::TokenFeeRateRemoved(token));
+            }
+        }
+    }
+
+    /// Grant `account` a fee exemption or reduced rate, applied when it is a stream sender.
+    /// Admin-only.
+    pub fn set_fee_discount(
+        env: Env,
+        admin: Address,
+        account: Address,
+        discount: FeeDiscount,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if let FeeDiscount::ReducedRate(rate) = discount {
+            if rate > MAX_FEE_RATE_BPS {
+                return Err(StreamError::InvalidFeeRate);
+            }
+        }
+
+        env.storage()
+            .persistent()
+            .set(&DataKey::FeeDiscount(account.clone()), &discount);
+
+        Self::publish_event(
+            &env,
+            ContractEvent::FeeDiscountUpdated(FeeDiscountUpdatedEvent { account, discount }),
+        );
+
+        Ok(())
+    }
+
+    /// Remove any fee discount granted to `account`. Admin-only.
+    pub fn remove_fee_discount(
+        env: Env,
+        admin: Address,
+        account: Address,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        env.storage()
+            .persistent()
+            .remove(&DataKey::FeeDiscount(account.clone()));
+
+        Self::publish_event(&env, ContractEvent::FeeDiscountRemoved(account));
+
+        Ok(())
+    }
+
+    pub fn get_fee_discount(env: Env, account: Address) -> Option<FeeDiscount> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::FeeDiscount(account))
+    }
+
+    /// Move an existing stream onto the fee rate currently in effect for its token.
+    /// Streams otherwise keep the rate they were created with. Only cuts can be applied,
+    /// so a live stream is never repriced upwards. Admin-only.
+    pub fn apply_current_fee_rate(
+        env: Env,
+        admin: Address,
+        stream_id: u64,
+    ) -> Result<u32, StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let storage = env.storage().persistent();
+        let stream_key = DataKey::Stream(stream_id);
+
+        let mut stream: Stream = match storage.get(&stream_key) {
+            Some(s) => s,
+            None => return Err(StreamError::StreamNotFound),
+        };
+
+        let old_fee_rate_bps = stream.fee_rate_bps;
+        let new_fee_rate_bps = Self::get_effective_fee(env.clone(), stream.token_address.clone());
+        if new_fee_rate_bps > old_fee_rate_bps {
+            return Err(StreamError::InvalidFeeRate);
+        }
+        stream.fee_rate_bps = new_fee_rate_bps;
+        storage.set(&stream_key, &stream);
+
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamFeeRateUpdated(StreamFeeRateUpdatedEvent {
+                stream_id,
+                old_fee_rate_bps,
+                new_fee_rate_bps: stream.fee_rate_bps,
+            }),
+        );
+
+        Ok(stream.fee_rate_bps)
+    }
+
+    /// Set the share of each fee (in basis points of the fee) paid to a stream's referrer.
+    /// Admin-only.
+    pub fn set_referral_share(env: Env, admin: Address, share_bps: u32) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if share_bps > 10_000 {
+            return Err(StreamError::InvalidShare);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::ReferralShare, &share_bps);
+
+        Self::publish_event(&env, ContractEvent::ReferralShareUpdated(share_bps));
+
+        Ok(())
+    }
+
+    pub fn get_referral_share(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ReferralShare)
+            .unwrap_or(0)
+    }
+
+    /// Schedule a new weighted list of treasury destinations for `distribute_fees`,
+    /// executable once the fee config delay has passed. Weights must be non-zero and add up
+    /// to 10_000 bps. An empty list switches back to paying the single
+    /// `ProtocolConfig.treasury` through `claim_fees`. Replaces any change already pending.
+    /// Admin-only.
+    pub fn queue_treasury_splits(
+        env: Env,
+        admin: Address,
+        splits: Vec<TreasurySplit>,
+    ) -> Result<PendingTreasurySplits, StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if splits.len() > MAX_TREASURY_SPLITS {
+            return Err(StreamError::InvalidShare);
+        }
+
+        if !splits.is_empty() {
+            let mut total: u32 = 0;
+            for split in splits.iter() {
+                if split.weight_bps == 0 {
+                    return Err(StreamError::InvalidShare);
+                }
+                total = total.saturating_add(split.weight_bps);
+            }
+            if total != 10_000 {
+                return Err(StreamError::InvalidShare);
+            }
+        }
+
+        let eta = env
+            .ledger()
+            .timestamp()
+            .saturating_add(Self::get_fee_config_delay(env.clone()));
+        let pending = PendingTreasurySplits { splits, eta };
+        env.storage()
+            .instance()
+            .set(&DataKey::PendingTreasurySplits, &pending);
+
+        Self::publish_event(&env, ContractEvent::TreasurySplitsQueued(pending.clone()));
+
+        Ok(pending)
+    }
+
+    /// Apply the pending treasury splits once their `eta` has been reached. Admin-only.
+    pub fn execute_treasury_splits(env: Env, admin: Address) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let pending = Self::get_pending_treasury_splits(env.clone())
+            .ok_or(StreamError::NoPendingTreasurySplits)?;
+
+        if env.ledger().timestamp() < pending.eta {
+            return Err(StreamError::TimelockNotExpired);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::TreasurySplits, &pending.splits);
+        env.storage().instance().remove(&DataKey::PendingTreasurySplits);
+
+        Self::publish_event(&env, ContractEvent::TreasurySplitsUpdated(pending.splits));
+
+        Ok(())
+    }
+
+    /// Drop the pending treasury split change. Admin-only.
+    pub fn cancel_treasury_splits(env: Env, admin: Address) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let pending = Self::get_pending_treasury_splits(env.clone())
+            .ok_or(StreamError::NoPendingTreasurySplits)?;
+        env.storage().instance().remove(&DataKey::PendingTreasurySplits);
+
+        Self::publish_event(&env, ContractEvent::TreasurySplitsCancelled(pending));
+
+        Ok(())
+    }
+
+    pub fn get_pending_treasury_splits(env: Env) -> Option<PendingTreasurySplits> {
+        env.storage()
+            .instance()
+            .get(&DataKey::PendingTreasurySplits)
+    }
+
+    pub fn get_treasury_splits(env: Env) -> Vec<TreasurySplit> {
+        env.storage()
+            .instance()
+            .get(&DataKey::TreasurySplits)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Set the smallest fee charged per operation in `token`, so fees can't be dodged by
+    /// splitting deposits into tiny amounts. 0 removes the minimum. Admin-only.
+    /// Existing streams keep the minimum they were created with.
+    pub fn set_min_fee(
+        env: Env,
+        admin: Address,
+        token: Address,
+        min_fee: i128,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        if min_fee < 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        let key = DataKey::MinFee(token.clone());
+        if min_fee == 0 {
+            env.storage().persistent().remove(&key);
+        } else {
+            env.storage().persistent().set(&key, &min_fee);
+        }
+
+        Self::publish_event(
+            &env,
+            ContractEvent::MinFeeUpdated(MinFeeUpdatedEvent { token, min_fee }),
+        );
+
+        Ok(())
+    }
+
+    pub fn get_min_fee(env: Env, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MinFee(token))
+            .unwrap_or(0)
+    }
+
+    // ─── Admin: Token Allowlist ──────────────────────────────────────
+
+    /// Turn enforcement of the token allowlist on or off. Admin-only.
+    /// While disabled, `create_stream` accepts any token.
+    pub fn set_allowlist_enabled(
+        env: Env,
+        admin: Address,
+        enabled: bool,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        env.storage()
+            .instance()
+            .set(&DataKey::AllowlistEnabled, &enabled);
+
+        Self::publish_event(&env, ContractEvent::AllowlistEnabledUpdated(enabled));
+
+        Ok(())
+    }
+
+    /// Add `token` to, or remove it from, the allowlist. Admin-only.
+    pub fn set_token_allowed(
+        env: Env,
+        admin: Address,
+        token: Address,
+        allowed: bool,
+    ) -> Result<(), StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let key = DataKey::AllowedToken(token.clone());
+        if allowed {
+            env.storage().persistent().set(&key, &true);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+
+        Self::publish_event(
+            &env,
+            ContractEvent::TokenAllowedUpdated(TokenAllowedUpdatedEvent { token, allowed }),
+        );
+
+        Ok(())
+    }
+
+    pub fn is_allowlist_enabled(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::AllowlistEnabled)
+            .unwrap_or(false)
+    }
+
+    /// Whether `token` is on the allowlist, regardless of whether the allowlist is enforced.
+    pub fn is_token_allowed(env: Env, token: Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::AllowedToken(token))
+    }
+
+    fn require_token_allowed(env: &Env, token: &Address) -> Result<(), StreamError> {
+        if Self::is_allowlist_enabled(env.clone())
+            && !Self::is_token_allowed(env.clone(), token.clone())
+        {
+            return Err(StreamError::TokenNotAllowed);
+        }
+        Ok(())
+    }
+
+    // ─── Fee Collection ──────────────────────────────────────────────
+
+    /// Deducts protocol fee at the stream's snapshotted rate from `amount` and accrues it for
+    /// the treasury to collect with `claim_fees`, less the referrer's share if the stream has one.
+    /// Returns the net amount (amount - fee). If no config or fee is 0, returns `amount` unchanged.
+    /// Any fee discount granted to the stream's sender is applied on top of the base rate.
+    fn collect_fee(
+        env: &Env,
+        stream: &Stream,
+        amount: i128,
+        stream_id: u64,
+    ) -> Result<i128, StreamError> {
+        let config: Option<ProtocolConfig> = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolConfig);
+
+        let cfg = match config {
+            Some(cfg) => cfg,
+            None => return Ok(amount),
+        };
+
+        let token_address = &stream.token_address;
+        let base_rate_bps = stream.fee_rate_bps;
+        let fee_rate_bps = Self::apply_fee_discount(env, &stream.sender, base_rate_bps)
+            .min(stream.discounted_fee_rate_bps);
+        let discount_bps = base_rate_bps - fee_rate_bps;
+
+        // A minimum fee that would swallow the whole amount is only waived on a stream's final
+        // payout, so its last tokens still reach the recipient. Any other deposit or withdrawal
+        // that small is rejected rather than letting fees be dodged in tiny chunks.
+        let min_fee = if fee_rate_bps > 0 && amount <= stream.min_fee {
+            let unwithdrawn = checked_sub(stream.deposited_amount, stream.withdrawn_amount)?;
+            match stream.fee_mode {
+                FeeMode::OnWithdraw if amount == unwithdrawn => 0,
+                _ => return Err(StreamError::AmountTooLow),
+            }
+        } else {
+            stream.min_fee
+        };
+        let fee = Self::compute_fee(amount, fee_rate_bps, stream.fee_rounding, min_fee)?;
+        let referrer_amount = match &stream.referrer {
+            Some(_) => bps_of(fee, Self::get_referral_share(env.clone()))?,
+            None => 0,
+        };
+
+        if fee > 0 {
+            Self::update_stats(env, token_address, |stats| {
+                stats.total_fees = checked_add(stats.total_fees, fee)?;
+                Ok(())
+            })?;
+        }
+        if fee > referrer_amount {
+            let key = DataKey::AccruedFees(token_address.clone());
+            let accrued: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+            let accrued = checked_add(accrued, checked_sub(fee, referrer_amount)?)?;
+            env.storage().persistent().set(&key, &accrued);
+        }
+        if referrer_amount > 0 {
+            if let Some(referrer) = &stream.referrer {
+                let key = DataKey::ReferralFees(referrer.clone(), token_address.clone());
+                let owed: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+                let owed = checked_add(owed, referrer_amount)?;
+                env.storage().persistent().set(&key, &owed);
+            }
+        }
+
+        // Waived fees are still reported so the discount shows up in revenue reconciliation.
+        if fee > 0 || discount_bps > 0 {
+            let mut recipients = Self::get_treasury_splits(env.clone());
+            if recipients.is_empty() {
+                recipients.push_back(TreasurySplit {
+                    destination: cfg.treasury,
+                    weight_bps: 10_000,
+                });
+            }
+            Self::publish_stream_event(
+                env,
+                stream_id,
+                stream,
+                ContractEvent::FeeCollected(FeeCollectedEvent {
+                    stream_id,
+                    recipients,
+                    fee_amount: fee,
+                    token: token_address.clone(),
+                    discount_bps,
+                    fee_mode: stream.fee_mode,
+                    referrer: stream.referrer.clone(),
+                    referrer_amount,
+                }),
+            );
+        }
+        checked_sub(amount, fee)
+    }
+
+    /// Fee on `amount` at `fee_rate_bps`, rounded as given and raised to `min_fee`.
+    /// A zero rate (e.g. a full exemption) is never charged the minimum, and the fee never
+    /// exceeds `amount`.
+    fn compute_fee(
+        amount: i128,
+        fee_rate_bps: u32,
+        rounding: FeeRounding,
+        min_fee: i128,
+    ) -> Result<i128, StreamError> {
+        if fee_rate_bps == 0 || amount <= 0 {
+            return Ok(0);
+        }
+
+        let scaled = checked_mul(amount, fee_rate_bps as i128)?;
+        let fee = match rounding {
+            FeeRounding::Floor => scaled / 10_000,
+            FeeRounding::Ceil => checked_add(scaled, 9_999)? / 10_000,
+        };
+
+        Ok(fee.max(min_fee).min(amount))
+    }
+
+    /// Fee that an operation of `amount` in `token` would currently be charged, before any
+    /// sender discount. Returns 0 if the protocol has not been initialized.
+    pub fn preview_fee(env: Env, token: Address, amount: i128) -> Result<i128, StreamError> {
+        match Self::get_fee_config(env.clone()) {
+            Some(cfg) => {
+                let fee_rate_bps = Self::resolve_fee_rate(&env, &cfg, &token);
+                let min_fee = Self::get_min_fee(env.clone(), token);
+                Self::compute_fee(amount, fee_rate_bps, cfg.fee_rounding, min_fee)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Per-token override if one is set, otherwise the global `fee_rate_bps`.
+    fn resolve_fee_rate(env: &Env, config: &ProtocolConfig, token: &Address) -> u32 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::TokenFeeRate(token.clone()))
+            .unwrap_or(config.fee_rate_bps)
+    }
+
+    /// Transfers all fees accrued in `token` to the treasury. Requires the treasury's auth.
+    /// Fees go to whichever treasury is configured at claim time. Once treasury splits are
+    /// configured, fees can only leave through `distribute_fees`.
+    pub fn claim_fees(env: Env, token: Address) -> Result<i128, StreamError> {
+        let config: ProtocolConfig = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolConfig)
+            .ok_or(StreamError::NotInitialized)?;
+        config.treasury.require_auth();
+
+        if !Self::get_treasury_splits(env.clone()).is_empty() {
+            return Err(StreamError::TreasurySplitsActive);
+        }
+
+        let key = DataKey::AccruedFees(token.clone());
+        let amount: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        if amount <= 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        env.storage().persistent().remove(&key);
+        let token_client = token::Client::new(&env, &token);
+        token_client.transfer(&env.current_contract_address(), &config.treasury, &amount);
+        Self::add_locked(&env, &token, -amount)?;
+
+        Self::publish_event(
+            &env,
+            ContractEvent::FeesClaimed(FeesClaimedEvent {
+                token,
+                treasury: config.treasury,
+                amount,
+            }),
+        );
+
+        Ok(amount)
+    }
+
+    /// Pays out all fees accrued in `token` to the treasury splits by weight. Callable by anyone.
+    /// Rounding dust goes to the last destination.
+    pub fn distribute_fees(env: Env, token: Address) -> Result<i128, StreamError> {
+        let splits = Self::get_treasury_splits(env.clone());
+        if splits.is_empty() {
+            return Err(StreamError::NoTreasurySplits);
+        }
+
+        let key = DataKey::AccruedFees(token.clone());
+        let amount: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        if amount <= 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        env.storage().persistent().remove(&key);
+        let token_client = token::Client::new(&env, &token);
+        let contract_address = env.current_contract_address();
+        Self::add_locked(&env, &token, -amount)?;
+
+        let mut remaining = amount;
+        let last = splits.len() - 1;
+        for (i, split) in splits.iter().enumerate() {
+            let share = if i as u32 == last {
+                remaining
+            } else {
+                bps_of(amount, split.weight_bps)?
+            };
+            remaining = checked_sub(remaining, share)?;
+            if share == 0 {
+                continue;
+            }
+
+            token_client.transfer(&contract_address, &split.destination, &share);
+            Self::publish_event(
+                &env,
+                ContractEvent::FeesClaimed(FeesClaimedEvent {
+                    token: token.clone(),
+                    treasury: split.destination,
+                    amount: share,
+                }),
+            );
+        }
+
+        Ok(amount)
+    }
+
+    /// Fees collected in `token` that the treasury has not claimed yet.
+    pub fn get_accrued_fees(env: Env, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AccruedFees(token))
+            .unwrap_or(0)
+    }
+
+    /// Transfers the referral fees `referrer` has earned in `token`. Requires the referrer's auth.
+    pub fn claim_referral_fees(
+        env: Env,
+        referrer: Address,
+        token: Address,
+    ) -> Result<i128, StreamError> {
+        referrer.require_auth();
+
+        let key = DataKey::ReferralFees(referrer.clone(), token.clone());
+        let amount: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        if amount <= 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        env.storage().persistent().remove(&key);
+        let token_client = token::Client::new(&env, &token);
+        token_client.transfer(&env.current_contract_address(), &referrer, &amount);
+        Self::add_locked(&env, &token, -amount)?;
+
+        Self::publish_event(
+            &env,
+            ContractEvent::ReferralFeesClaimed(ReferralFeesClaimedEvent {
+                referrer,
+                token,
+                amount,
+            }),
+        );
+
+        Ok(amount)
+    }
+
+    /// Referral fees earned by `referrer` in `token` and not yet claimed.
+    pub fn get_referral_fees(env: Env, referrer: Address, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ReferralFees(referrer, token))
+            .unwrap_or(0)
+    }
+
+    /// Rate charged to `sender` given the token's `rate_bps`, after any fee discount.
+    fn apply_fee_discount(env: &Env, sender: &Address, rate_bps: u32) -> u32 {
+        let discount: Option<FeeDiscount> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::FeeDiscount(sender.clone()));
+
+        match discount {
+            Some(FeeDiscount::Exempt) => 0,
+            Some(FeeDiscount::ReducedRate(reduced)) => reduced.min(rate_bps),
+            None => rate_bps,
+        }
+    }
+
+    /// Fee rate (in basis points) that `collect_fee` will charge for `token`.
+    /// Returns 0 if the protocol has not been initialized.
+    pub fn get_effective_fee(env: Env, token: Address) -> u32 {
+        let config: Option<ProtocolConfig> = env
+            .storage()
+            .instance()
+            .get(&DataKey::ProtocolConfig);
+
+        match config {
+            Some(cfg) => Self::resolve_fee_rate(&env, &cfg, &token),
+            None => 0,
+        }
+    }
+
+    // ─── Stream Operations ───────────────────────────────────────────
+
+    /// Creates a stream of `amount` (minus protocol fee) over `duration` seconds.
+    /// `options` carries the rent budget, referrer and metadata, all of which may be left
+    /// at their defaults.
+    pub fn create_stream(
+        env: Env,
+        sender: Address,
+        recipient: Address,
+        token_address: Address,
+        amount: i128,
+        duration: u64,
+        options: CreateStreamOptions,
+    ) -> Result<u64, StreamError> {
+        sender.require_auth();
+
+        let CreateStreamOptions {
+            rent_budget,
+            referrer,
+            metadata,
+        } = options;
+
+        if Self::is_emergency_mode(env.clone()) {
+            return Err(StreamError::EmergencyStopEnabled);
+        }
+
+        if amount <= 0 || rent_budget < 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        if duration == 0 {
+            return Err(StreamError::InvalidDuration);
+        }
+
+        if sender == recipient {
+            return Err(StreamError::SelfStream);
+        }
+
+        if recipient == env.current_contract_address() {
+            return Err(StreamError::InvalidRecipient);
+        }
+
+        Self::validate_metadata(&metadata)?;
 
-        let stream = Stream {
+        let config = Self::get_fee_config(env.clone());
+        if let Some(cfg) = &config {
+            if amount < cfg.min_amount {
+                return Err(StreamError::AmountTooLow);
+            }
+            if duration < cfg.min_duration {
+                return Err(StreamError::DurationTooShort);
+            }
+            if cfg.max_duration != 0 && duration > cfg.max_duration {
+                return Err(StreamError::DurationTooLong);
+            }
+        }
+
+        Self::require_token_allowed(&env, &token_address)?;
+
+        if referrer.as_ref() == Some(&sender) {
+            return Err(StreamError::InvalidReferrer);
+        }
+
+        let stream_id = Self::get_next_stream_id(&env)?;
+        let start_time = env.ledger().timestamp();
+
+        // Credit what actually arrived, not what was requested, so fee-on-transfer
+        // tokens can't leave the stream overstated.
+        let amount = Self::receive_tokens(&env, &token_address, &sender, amount)?;
+        let rent_budget = if rent_budget > 0 {
+            Self::receive_tokens(&env, &token_address, &sender, rent_budget)?
+        } else {
+            0
+        };
+        Self::add_locked(&env, &token_address, checked_add(amount, rent_budget)?)?;
+        Self::update_stats(&env, &token_address, |stats| {
+            stats.total_deposited = checked_add(stats.total_deposited, amount)?;
+            stats.active_streams = stats
+                .active_streams
+                .checked_add(1)
+                .ok_or(StreamError::ArithmeticOverflow)?;
+            stats.total_streams = stats
+                .total_streams
+                .checked_add(1)
+                .ok_or(StreamError::ArithmeticOverflow)?;
+            Ok(())
+        })?;
+
+        // Fee rate, discount, mode, rounding and minimum are snapshotted so later config changes
+        // don't reprice this stream.
+        let fee_rate_bps = Self::get_effective_fee(env.clone(), token_address.clone());
+        let mut stream = Stream {
             sender: sender.clone(),
             recipient: recipient.clone(),
             token_address: token_address.clone(),
-            rate_per_second,
-            deposited_amount: net_amount,
+            rate_per_second: 0,
+            deposited_amount: 0,
             withdrawn_amount: 0,
             start_time,
             last_update_time: start_time,
             is_active: true,
+            rent_balance: rent_budget,
+            last_bump_time: start_time,
+            last_bump_ledger: env.ledger().sequence(),
+            fee_rate_bps,
+            discounted_fee_rate_bps: Self::apply_fee_discount(&env, &sender, fee_rate_bps),
+            fee_mode: config.as_ref().map_or(FeeMode::OnDeposit, |cfg| cfg.fee_mode),
+            fee_rounding: config.as_ref().map_or(FeeRounding::Floor, |cfg| cfg.fee_rounding),
+            min_fee: Self::get_min_fee(env.clone(), token_address.clone()),
+            referrer,
+            metadata,
         };
 
+        // Deduct protocol fee (if configured) and get net amount for the stream
+        let net_amount = match stream.fee_mode {
+            FeeMode::OnDeposit => Self::collect_fee(&env, &stream, amount, stream_id)?,
+            FeeMode::OnWithdraw => amount,
+        };
+        let rate_per_second = net_amount / (duration as i128);
+        if rate_per_second == 0 {
+            return Err(StreamError::RateTooLow);
+        }
+        stream.rate_per_second = rate_per_second;
+        stream.deposited_amount = net_amount;
+
+        let stream_key = DataKey::Stream(stream_id);
+        env.storage().persistent().set(&stream_key, &stream);
         env.storage()
             .persistent()
-            .set(&DataKey::Stream(stream_id), &stream);
-
-        env.events().publish(
-            (Symbol::new(&env, "stream_created"), stream_id),
-            StreamCreatedEvent {
+            .extend_ttl(&stream_key, STREAM_LIFETIME_THRESHOLD, STREAM_BUMP_AMOUNT);
+
+        Self::index_stream(
+            &env,
+            DataKey::SenderStreamCount(sender.clone()),
+            |n| DataKey::SenderStream(sender.clone(), n),
+            stream_id,
+        )?;
+        Self::index_stream(
+            &env,
+            DataKey::RecipientStreamCount(recipient.clone()),
+            |n| DataKey::RecipientStream(recipient.clone(), n),
+            stream_id,
+        )?;
+
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamCreated(StreamCreatedEvent {
                 stream_id,
                 sender,
                 recipient,
                 rate: rate_per_second,
                 token_address,
                 start_time,
-            },
+                metadata: stream.metadata.clone(),
+            }),
+        );
+
+        Ok(stream_id)
+    }
+
+    /// Replaces the memo, category and reference hash of a stream. Sender-only.
+    pub fn update_stream_metadata(
+        env: Env,
+        sender: Address,
+        stream_id: u64,
+        metadata: StreamMetadata,
+    ) -> Result<(), StreamError> {
+        sender.require_auth();
+
+        let storage = env.storage().persistent();
+        let stream_key = DataKey::Stream(stream_id);
+        let mut stream: Stream = storage.get(&stream_key).ok_or(StreamError::StreamNotFound)?;
+
+        if stream.sender != sender {
+            return Err(StreamError::Unauthorized);
+        }
+
+        Self::validate_metadata(&metadata)?;
+
+        let old_metadata = stream.metadata.clone();
+        stream.metadata = metadata.clone();
+        storage.set(&stream_key, &stream);
+
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamMetadataUpdated(StreamMetadataUpdatedEvent {
+                stream_id,
+                old_metadata,
+                new_metadata: metadata,
+            }),
         );
 
-        stream_id
+        Ok(())
+    }
+
+    fn validate_metadata(metadata: &StreamMetadata) -> Result<(), StreamError> {
+        if let Some(memo) = &metadata.memo {
+            if memo.len() > MAX_MEMO_LEN {
+                return Err(StreamError::MemoTooLong);
+            }
+        }
+        Ok(())
     }
 
-    fn get_next_stream_id(env: &Env) -> u64 {
+    fn get_next_stream_id(env: &Env) -> Result<u64, StreamError> {
         let counter: u64 = env
             .storage()
             .instance()
             .get(&DataKey::StreamCounter)
             .unwrap_or(0);
-        let next_id = counter + 1;
+        let next_id = counter.checked_add(1).ok_or(StreamError::ArithmeticOverflow)?;
         env.storage()
             .instance()
             .set(&DataKey::StreamCounter, &next_id);
-        next_id
+        Ok(next_id)
     }
 
 
 fn calculate_claimable(stream: &Stream, now: u64) -> i128 {
     let elapsed = now.saturating_sub(stream.last_update_time);
 
-    let streamed = match (elapsed as i128).checked_mul(stream.rate_per_second) {
-        Some(val) => val,
-        None => i128::MAX,
-    };
+    let streamed = (elapsed as i128)
+        .checked_mul(stream.rate_per_second)
+        .unwrap_or(i128::MAX);
 
     let remaining = stream
         .deposited_amount
@@ -318,8 +2028,10 @@ fn calculate_claimable(stream: &Stream, now: u64) -> i128 {
 
 
 
-    pub fn withdraw(env: Env, recipient: Address, stream_id: u64) -> Result<i128, StreamError> {
-        recipient.require_auth();
+    /// Pays out everything accrued so far. `caller` is the recipient or an operator it has
+    /// approved; either way the funds go to the recipient.
+    pub fn withdraw(env: Env, caller: Address, stream_id: u64) -> Result<i128, StreamError> {
+        caller.require_auth();
 
         let storage = env.storage().persistent();
         let stream_key = DataKey::Stream(stream_id);
@@ -329,7 +2041,10 @@ fn calculate_claimable(stream: &Stream, now: u64) -> i128 {
             None => return Err(StreamError::StreamNotFound),
         };
 
-        if stream.recipient != recipient {
+        let recipient = stream.recipient.clone();
+        if caller != recipient
+            && !Self::is_operator(env.clone(), recipient.clone(), caller, stream_id)
+        {
             return Err(StreamError::Unauthorized);
         }
 
@@ -343,28 +2058,61 @@ fn calculate_claimable(stream: &Stream, now: u64) -> i128 {
 if claimable <= 0 {
     return Err(StreamError::InvalidAmount);
 }
-        let token_client = token::Client::new(&env, &stream.token_address);
+        Self::pay_out(&env, stream_id, &mut stream, claimable)
+    }
+
+    /// Sends `claimable` (net of any withdrawal fee) to the recipient, closing the stream
+    /// once everything deposited has been paid. Returns the amount sent.
+    fn pay_out(
+        env: &Env,
+        stream_id: u64,
+        stream: &mut Stream,
+        claimable: i128,
+    ) -> Result<i128, StreamError> {
+        let recipient = stream.recipient.clone();
+        let payout = match stream.fee_mode {
+            FeeMode::OnDeposit => claimable,
+            FeeMode::OnWithdraw => Self::collect_fee(env, stream, claimable, stream_id)?,
+        };
+
+        let token_client = token::Client::new(env, &stream.token_address);
         let contract_address = env.current_contract_address();
-        token_client.transfer(&contract_address, &recipient, &claimable);
+        token_client.transfer(&contract_address, &recipient, &payout);
+        Self::add_locked(env, &stream.token_address, -payout)?;
 
-        stream.withdrawn_amount += claimable;
+        stream.withdrawn_amount = checked_add(stream.withdrawn_amount, claimable)?;
         stream.last_update_time = env.ledger().timestamp();
-        if stream.withdrawn_amount >= stream.deposited_amount {
+        let depleted = stream.withdrawn_amount >= stream.deposited_amount;
+        if depleted {
             stream.is_active = false;
+            Self::refund_rent(env, stream)?;
+            Self::refund_push_tip(env, stream_id, stream)?;
         }
-        storage.set(&stream_key, &stream);
+        Self::update_stats(env, &stream.token_address, |stats| {
+            stats.total_withdrawn = checked_add(stats.total_withdrawn, payout)?;
+            if depleted {
+                stats.active_streams = stats.active_streams.saturating_sub(1);
+            }
+            Ok(())
+        })?;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Stream(stream_id), &*stream);
 
-        env.events().publish(
-            (Symbol::new(&env, "tokens_withdrawn"), stream_id),
-            TokensWithdrawnEvent {
+        Self::publish_stream_event(
+            env,
+            stream_id,
+            stream,
+            ContractEvent::TokensWithdrawn(TokensWithdrawnEvent {
                 stream_id,
                 recipient,
-                amount: claimable,
+                amount: payout,
                 timestamp: stream.last_update_time,
-            },
+            }),
         );
+        Self::check_runway(env, stream_id, stream)?;
 
-        Ok(claimable)
+        Ok(payout)
     }
 
     pub fn cancel_stream(env: Env, sender: Address, stream_id: u64) -> Result<(), StreamError> {
@@ -388,18 +2136,26 @@ if claimable <= 0 {
 
         stream.is_active = false;
         stream.last_update_time = env.ledger().timestamp();
+        Self::refund_rent(&env, &mut stream)?;
+        Self::refund_push_tip(&env, stream_id, &stream)?;
+        Self::update_stats(&env, &stream.token_address, |stats| {
+            stats.active_streams = stats.active_streams.saturating_sub(1);
+            Ok(())
+        })?;
         let recipient = stream.recipient.clone();
         let amount_withdrawn = stream.withdrawn_amount;
         storage.set(&stream_key, &stream);
 
-        env.events().publish(
-            (Symbol::new(&env, "stream_cancelled"), stream_id),
-            StreamCancelledEvent {
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamCancelled(StreamCancelledEvent {
                 stream_id,
                 sender,
                 recipient,
                 amount_withdrawn,
-            },
+            }),
         );
 
         Ok(())
@@ -437,35 +2193,708 @@ if claimable <= 0 {
             return Err(StreamError::StreamInactive);
         }
 
-        // Transfer full amount from sender to contract
-        let token_client = token::Client::new(&env, &stream.token_address);
-        let contract_address = env.current_contract_address();
-        token_client.transfer(&sender, &contract_address, &amount);
+        Self::require_token_allowed(&env, &stream.token_address)?;
+
+        let amount = Self::receive_tokens(&env, &stream.token_address, &sender, amount)?;
+        Self::add_locked(&env, &stream.token_address, amount)?;
+        Self::update_stats(&env, &stream.token_address, |stats| {
+            stats.total_deposited = checked_add(stats.total_deposited, amount)?;
+            Ok(())
+        })?;
 
         // Deduct protocol fee (if configured) and add net amount to stream
-        let net_amount = Self::collect_fee(&env, &stream.token_address, amount, stream_id);
+        let net_amount = match stream.fee_mode {
+            FeeMode::OnDeposit => Self::collect_fee(&env, &stream, amount, stream_id)?,
+            FeeMode::OnWithdraw => amount,
+        };
 
-        stream.deposited_amount += net_amount;
+        stream.deposited_amount = checked_add(stream.deposited_amount, net_amount)?;
         stream.last_update_time = env.ledger().timestamp();
 
         storage.set(&stream_key, &stream);
+        storage.remove(&DataKey::DepletionNotified(stream_id));
 
-        env.events().publish(
-            (Symbol::new(&env, "stream_topped_up"), stream_id),
-            StreamToppedUpEvent {
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamToppedUp(StreamToppedUpEvent {
                 stream_id,
                 sender,
                 amount: net_amount,
                 new_deposited_amount: stream.deposited_amount,
+            }),
+        );
+        Self::check_runway(&env, stream_id, &stream)?;
+
+        Ok(())
+    }
+
+    /// Reports how close a stream is to running dry. Callable by anyone.
+    /// Returns `true` once accrual has caught up with the deposit, emitting `StreamDepleted` the
+    /// first time (again only after a top-up); otherwise emits `StreamRunwayLow` if the runway is
+    /// under the threshold.
+    pub fn poke(env: Env, stream_id: u64) -> Result<bool, StreamError> {
+        let stream: Stream = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Stream(stream_id))
+            .ok_or(StreamError::StreamNotFound)?;
+
+        if !stream.is_active {
+            return Err(StreamError::StreamInactive);
+        }
+
+        let now = env.ledger().timestamp();
+        let remaining = checked_sub(stream.deposited_amount, stream.withdrawn_amount)?;
+        let claimable = Self::calculate_claimable(&stream, now);
+        if claimable < remaining {
+            Self::check_runway(&env, stream_id, &stream)?;
+            return Ok(false);
+        }
+
+        let notified_key = DataKey::DepletionNotified(stream_id);
+        if env.storage().persistent().has(&notified_key) {
+            return Ok(true);
+        }
+        env.storage().persistent().set(&notified_key, &true);
+
+        let rate = stream.rate_per_second;
+        let secs_rounded_up = checked_add(remaining, checked_sub(rate, 1)?)? / rate;
+        let secs_to_drain = u64::try_from(secs_rounded_up).unwrap_or(u64::MAX);
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamDepleted(StreamDepletedEvent {
+                stream_id,
+                unclaimed: remaining,
+                depleted_at: stream.last_update_time.saturating_add(secs_to_drain),
+            }),
+        );
+
+        Ok(true)
+    }
+
+    /// Emits `StreamRunwayLow` if an active stream has less than the runway threshold left.
+    fn check_runway(env: &Env, stream_id: u64, stream: &Stream) -> Result<(), StreamError> {
+        let threshold = Self::get_runway_threshold(env.clone());
+        if !stream.is_active || threshold == 0 || stream.rate_per_second <= 0 {
+            return Ok(());
+        }
+
+        let now = env.ledger().timestamp();
+        let unwithdrawn = checked_sub(stream.deposited_amount, stream.withdrawn_amount)?;
+        let remaining = checked_sub(unwithdrawn, Self::calculate_claimable(stream, now))?;
+        let runway_secs = u64::try_from(remaining / stream.rate_per_second).unwrap_or(u64::MAX);
+        if runway_secs >= threshold {
+            return Ok(());
+        }
+
+        Self::publish_stream_event(
+            env,
+            stream_id,
+            stream,
+            ContractEvent::StreamRunwayLow(StreamRunwayLowEvent {
+                stream_id,
+                remaining,
+                runway_secs,
+            }),
+        );
+        Ok(())
+    }
+
+    // ─── Withdrawal Operators ────────────────────────────────────────
+
+    /// Lets `operator` call `withdraw` for one of `recipient`'s streams, or all of them.
+    /// Payouts still go to the recipient. Recipient-only.
+    pub fn approve_operator(
+        env: Env,
+        recipient: Address,
+        operator: Address,
+        scope: OperatorScope,
+    ) -> Result<(), StreamError> {
+        recipient.require_auth();
+
+        if let OperatorScope::Stream(stream_id) = scope {
+            let stream =
+                Self::get_stream(env.clone(), stream_id).ok_or(StreamError::StreamNotFound)?;
+            if stream.recipient != recipient {
+                return Err(StreamError::Unauthorized);
+            }
+        }
+
+        let approval = OperatorApproval { operator, scope };
+        let mut approvals = Self::get_operator_approvals(env.clone(), recipient.clone());
+        if !approvals.contains(&approval) {
+            if approvals.len() >= MAX_OPERATOR_APPROVALS {
+                return Err(StreamError::TooManyOperators);
+            }
+            approvals.push_back(approval.clone());
+            env.storage()
+                .persistent()
+                .set(&DataKey::OperatorApprovals(recipient.clone()), &approvals);
+        }
+
+        Self::publish_operator_event(
+            &env,
+            OperatorApprovalEvent {
+                recipient,
+                operator: approval.operator,
+                scope: approval.scope,
+            },
+            ContractEvent::OperatorApproved,
+        );
+
+        Ok(())
+    }
+
+    /// Withdraws an approval previously granted with `approve_operator`. Recipient-only.
+    pub fn revoke_operator(
+        env: Env,
+        recipient: Address,
+        operator: Address,
+        scope: OperatorScope,
+    ) -> Result<(), StreamError> {
+        recipient.require_auth();
+
+        let approval = OperatorApproval { operator, scope };
+        let mut approvals = Self::get_operator_approvals(env.clone(), recipient.clone());
+        let index = approvals
+            .first_index_of(&approval)
+            .ok_or(StreamError::OperatorNotApproved)?;
+        approvals.remove(index);
+        env.storage()
+            .persistent()
+            .set(&DataKey::OperatorApprovals(recipient.clone()), &approvals);
+
+        Self::publish_operator_event(
+            &env,
+            OperatorApprovalEvent {
+                recipient,
+                operator: approval.operator,
+                scope: approval.scope,
             },
+            ContractEvent::OperatorRevoked,
+        );
+
+        Ok(())
+    }
+
+    pub fn get_operator_approvals(env: Env, recipient: Address) -> Vec<OperatorApproval> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OperatorApprovals(recipient))
+            .unwrap_or(Vec::new(&env))
+    }
+
+    /// Whether `operator` may withdraw from `stream_id` on behalf of `recipient`.
+    pub fn is_operator(env: Env, recipient: Address, operator: Address, stream_id: u64) -> bool {
+        let scope = OperatorScope::Stream(stream_id);
+        Self::get_operator_approvals(env, recipient)
+            .iter()
+            .any(|a| a.operator == operator && (a.scope == OperatorScope::All || a.scope == scope))
+    }
+
+    // ─── Push Withdrawals ────────────────────────────────────────────
+
+    /// Adds `amount` to the stream's keeper tip budget and sets the most paid per
+    /// `push_withdraw` call. `amount` may be 0 to only change `per_call`. Sender-only.
+    pub fn fund_push_tip(
+        env: Env,
+        sender: Address,
+        stream_id: u64,
+        amount: i128,
+        per_call: i128,
+    ) -> Result<PushTip, StreamError> {
+        sender.require_auth();
+
+        if Self::is_emergency_mode(env.clone()) {
+            return Err(StreamError::EmergencyStopEnabled);
+        }
+
+        let stream = Self::get_stream(env.clone(), stream_id).ok_or(StreamError::StreamNotFound)?;
+        if stream.sender != sender {
+            return Err(StreamError::Unauthorized);
+        }
+        if !stream.is_active {
+            return Err(StreamError::StreamInactive);
+        }
+        if amount < 0 || per_call < 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+        Self::require_token_allowed(&env, &stream.token_address)?;
+
+        let received = if amount > 0 {
+            let received = Self::receive_tokens(&env, &stream.token_address, &sender, amount)?;
+            Self::add_locked(&env, &stream.token_address, received)?;
+            received
+        } else {
+            0
+        };
+
+        let current = Self::get_push_tip(env.clone(), stream_id);
+        let tip = PushTip {
+            balance: checked_add(current.map_or(0, |t| t.balance), received)?,
+            per_call,
+        };
+        env.storage()
+            .persistent()
+            .set(&DataKey::PushTip(stream_id), &tip);
+
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::PushTipFunded(PushTipFundedEvent {
+                stream_id,
+                amount: received,
+                per_call,
+                balance: tip.balance,
+            }),
+        );
+
+        Ok(tip)
+    }
+
+    pub fn get_push_tip(env: Env, stream_id: u64) -> Option<PushTip> {
+        env.storage().persistent().get(&DataKey::PushTip(stream_id))
+    }
+
+    /// Sends a stream's accrued balance to its recipient. Callable by anyone, but only once
+    /// `MIN_PUSH_INTERVAL_SECS` have accrued since the last payout, unless this payout
+    /// finishes the stream. The `keeper` is paid up to the sender's per-call tip.
+    /// Returns the tip paid.
+    pub fn push_withdraw(env: Env, keeper: Address, stream_id: u64) -> Result<i128, StreamError> {
+        keeper.require_auth();
+
+        let mut stream =
+            Self::get_stream(env.clone(), stream_id).ok_or(StreamError::StreamNotFound)?;
+        if !stream.is_active {
+            return Err(StreamError::StreamInactive);
+        }
+
+        let now = env.ledger().timestamp();
+        let claimable = Self::calculate_claimable(&stream, now);
+        if claimable <= 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+        let remaining = checked_sub(stream.deposited_amount, stream.withdrawn_amount)?;
+        if claimable < remaining
+            && now < stream.last_update_time.saturating_add(MIN_PUSH_INTERVAL_SECS)
+        {
+            return Err(StreamError::PushTooSoon);
+        }
+
+        // Settle the tip before paying out: a finishing payout refunds what's left of it.
+        let tip = match Self::get_push_tip(env.clone(), stream_id) {
+            Some(mut budget) => {
+                let tip = budget.per_call.min(budget.balance);
+                if tip > 0 {
+                    token::Client::new(&env, &stream.token_address).transfer(
+                        &env.current_contract_address(),
+                        &keeper,
+                        &tip,
+                    );
+                    Self::add_locked(&env, &stream.token_address, -tip)?;
+                    budget.balance = checked_sub(budget.balance, tip)?;
+                    env.storage()
+                        .persistent()
+                        .set(&DataKey::PushTip(stream_id), &budget);
+                }
+                tip
+            }
+            None => 0,
+        };
+
+        let amount = Self::pay_out(&env, stream_id, &mut stream, claimable)?;
+
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::PushWithdrawn(PushWithdrawnEvent {
+                stream_id,
+                keeper,
+                amount,
+                tip,
+            }),
         );
 
+        Ok(tip)
+    }
+
+    /// Returns any unused keeper tip budget to the sender.
+    fn refund_push_tip(env: &Env, stream_id: u64, stream: &Stream) -> Result<(), StreamError> {
+        let key = DataKey::PushTip(stream_id);
+        if let Some(tip) = env.storage().persistent().get::<_, PushTip>(&key) {
+            if tip.balance > 0 {
+                token::Client::new(env, &stream.token_address).transfer(
+                    &env.current_contract_address(),
+                    &stream.sender,
+                    &tip.balance,
+                );
+                Self::add_locked(env, &stream.token_address, -tip.balance)?;
+            }
+            env.storage().persistent().remove(&key);
+        }
+        Ok(())
+    }
+
+    // ─── Stream Rent ─────────────────────────────────────────────────
+
+    /// Extends the TTL of a stream's storage entry. Callable by anyone; the `keeper`
+    /// is paid from the stream's rent budget, spread evenly over the bump intervals
+    /// left until the stream runs dry. Returns the reward paid.
+    pub fn bump_stream(env: Env, keeper: Address, stream_id: u64) -> Result<i128, StreamError> {
+        keeper.require_auth();
+
+        let storage = env.storage().persistent();
+        let stream_key = DataKey::Stream(stream_id);
+
+        let mut stream: Stream = match storage.get(&stream_key) {
+            Some(s) => s,
+            None => return Err(StreamError::StreamNotFound),
+        };
+
+        if !stream.is_active {
+            return Err(StreamError::StreamInactive);
+        }
+
+        // Both clocks must have moved on: the time one paces rewards over the stream's runway,
+        // the ledger one guarantees the TTL is at the threshold so the extension isn't a no-op.
+        let now = env.ledger().timestamp();
+        let ledger = env.ledger().sequence();
+        if now < stream.last_bump_time.saturating_add(BUMP_INTERVAL_SECS)
+            || ledger < stream.last_bump_ledger.saturating_add(BUMP_INTERVAL_LEDGERS)
+        {
+            return Err(StreamError::BumpTooSoon);
+        }
+
+        let reward = Self::bump_reward(&stream, now)?;
+        if reward > 0 {
+            let token_client = token::Client::new(&env, &stream.token_address);
+            token_client.transfer(&env.current_contract_address(), &keeper, &reward);
+            Self::add_locked(&env, &stream.token_address, -reward)?;
+            stream.rent_balance = checked_sub(stream.rent_balance, reward)?;
+        }
+        stream.last_bump_time = now;
+        stream.last_bump_ledger = ledger;
+
+        storage.set(&stream_key, &stream);
+        storage.extend_ttl(&stream_key, STREAM_LIFETIME_THRESHOLD, STREAM_BUMP_AMOUNT);
+
+        Self::publish_stream_event(
+            &env,
+            stream_id,
+            &stream,
+            ContractEvent::StreamBumped(StreamBumpedEvent {
+                stream_id,
+                keeper,
+                reward,
+                rent_balance: stream.rent_balance,
+            }),
+        );
+
+        Ok(reward)
+    }
+
+    /// Share of the remaining rent budget for one bump, given how long the stream has left to run.
+    fn bump_reward(stream: &Stream, now: u64) -> Result<i128, StreamError> {
+        if stream.rent_balance <= 0 {
+            return Ok(0);
+        }
+
+        let unstreamed = checked_sub(stream.deposited_amount, stream.withdrawn_amount)?;
+        let runway = if stream.rate_per_second > 0 {
+            u64::try_from(unstreamed / stream.rate_per_second).unwrap_or(u64::MAX)
+        } else {
+            0
+        };
+        let end_time = stream.last_update_time.saturating_add(runway);
+        let intervals_left = end_time.saturating_sub(now) / BUMP_INTERVAL_SECS + 1;
+
+        Ok(stream.rent_balance / (intervals_left as i128))
+    }
+
+    /// Returns any unused rent budget to the sender. Called once the stream is no longer active.
+    fn refund_rent(env: &Env, stream: &mut Stream) -> Result<(), StreamError> {
+        if stream.rent_balance > 0 {
+            let token_client = token::Client::new(env, &stream.token_address);
+            token_client.transfer(
+                &env.current_contract_address(),
+                &stream.sender,
+                &stream.rent_balance,
+            );
+            Self::add_locked(env, &stream.token_address, -stream.rent_balance)?;
+            stream.rent_balance = 0;
+        }
+        Ok(())
+    }
+
+    // ─── Solvency ────────────────────────────────────────────────────
+
+    /// Total amount of `token` the contract owes to streams, rent and keeper tip budgets,
+    /// the treasury and referrers.
+    pub fn get_total_locked(env: Env, token: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::TotalLocked(token))
+            .unwrap_or(0)
+    }
+
+    /// Fails with `InvariantViolated` if the contract holds less `token` than it owes.
+    pub fn check_invariants(env: Env, token: Address) -> Result<(), StreamError> {
+        let balance = token::Client::new(&env, &token).balance(&env.current_contract_address());
+        if balance < Self::get_total_locked(env.clone(), token) {
+            return Err(StreamError::InvariantViolated);
+        }
+        Ok(())
+    }
+
+    /// Sends `token` held above the contract's obligations (e.g. tokens transferred to the
+    /// contract by mistake) to `to`. Never touches funds owed to streams or fee recipients.
+    /// Admin-only. Returns the amount swept.
+    pub fn sweep(
+        env: Env,
+        admin: Address,
+        token: Address,
+        to: Address,
+    ) -> Result<i128, StreamError> {
+        Self::require_admin(&env, &admin)?;
+
+        let token_client = token::Client::new(&env, &token);
+        let contract_address = env.current_contract_address();
+        let balance = token_client.balance(&contract_address);
+        let surplus = checked_sub(balance, Self::get_total_locked(env.clone(), token.clone()))?;
+        if surplus <= 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+
+        token_client.transfer(&contract_address, &to, &surplus);
+
+        Self::publish_event(
+            &env,
+            ContractEvent::TokensSwept(TokensSweptEvent {
+                token,
+                to,
+                amount: surplus,
+            }),
+        );
+
+        Ok(surplus)
+    }
+
+    /// Pulls `amount` from `from` and returns the balance delta the contract actually received.
+    fn receive_tokens(
+        env: &Env,
+        token: &Address,
+        from: &Address,
+        amount: i128,
+    ) -> Result<i128, StreamError> {
+        let token_client = token::Client::new(env, token);
+        let contract_address = env.current_contract_address();
+        let before = token_client.balance(&contract_address);
+        token_client.transfer(from, &contract_address, &amount);
+        let received = checked_sub(token_client.balance(&contract_address), before)?;
+        if received <= 0 {
+            return Err(StreamError::InvalidAmount);
+        }
+        Ok(received)
+    }
+
+    /// Adjusts the `TotalLocked` counter for `token` by `delta` as funds enter or leave.
+    fn add_locked(env: &Env, token: &Address, delta: i128) -> Result<(), StreamError> {
+        let key = DataKey::TotalLocked(token.clone());
+        let locked: i128 = env.storage().persistent().get(&key).unwrap_or(0);
+        env.storage()
+            .persistent()
+            .set(&key, &checked_add(locked, delta)?);
         Ok(())
     }
 
     pub fn get_stream(env: Env, stream_id: u64) -> Option<Stream> {
         env.storage().persistent().get(&DataKey::Stream(stream_id))
     }
+
+    /// Number of streams ever created; IDs run from 1 to this value.
+    pub fn stream_count(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::StreamCounter)
+            .unwrap_or(0)
+    }
+
+    /// Looks up a batch of streams by ID. Unknown IDs come back as `None`.
+    /// Only the first `MAX_PAGE_SIZE` IDs are read.
+    pub fn get_streams(env: Env, ids: Vec<u64>) -> Vec<(u64, Option<Stream>)> {
+        let mut streams = Vec::new(&env);
+        for stream_id in ids.iter().take(MAX_PAGE_SIZE as usize) {
+            streams.push_back((stream_id, Self::get_stream(env.clone(), stream_id)));
+        }
+        streams
+    }
+
+    /// Walks stream IDs from `start_id` upward, stopping at the stream counter.
+    /// At most `limit` entries are returned, capped at `MAX_PAGE_SIZE`.
+    pub fn list_streams(env: Env, start_id: u64, limit: u32) -> Vec<(u64, Option<Stream>)> {
+        let count = Self::stream_count(env.clone());
+        let mut streams = Vec::new(&env);
+        let mut stream_id = start_id.max(1);
+        while stream_id <= count && streams.len() < limit.min(MAX_PAGE_SIZE) {
+            streams.push_back((stream_id, Self::get_stream(env.clone(), stream_id)));
+            stream_id += 1;
+        }
+        streams
+    }
+
+    // ─── Events ──────────────────────────────────────────────────────
+
+    fn publish_event(env: &Env, event: ContractEvent) {
+        env.events().publish(
+            (Symbol::new(env, EVENT_NAMESPACE), Symbol::new(env, event.name())),
+            EventEnvelope {
+                version: EVENT_SCHEMA_VERSION,
+                event,
+            },
+        );
+    }
+
+    fn publish_stream_event(env: &Env, stream_id: u64, stream: &Stream, event: ContractEvent) {
+        env.events().publish(
+            (
+                Symbol::new(env, EVENT_NAMESPACE),
+                Symbol::new(env, event.name()),
+                stream_id,
+                stream.sender.clone(),
+                stream.recipient.clone(),
+            ),
+            EventEnvelope {
+                version: EVENT_SCHEMA_VERSION,
+                event,
+            },
+        );
+    }
+
+    /// Publishes an operator approval event with the recipient and operator as topics, followed
+    /// by the stream ID for approvals scoped to one stream.
+    fn publish_operator_event(
+        env: &Env,
+        approval: OperatorApprovalEvent,
+        wrap: fn(OperatorApprovalEvent) -> ContractEvent,
+    ) {
+        let recipient = approval.recipient.clone();
+        let operator = approval.operator.clone();
+        let scope = approval.scope.clone();
+        let event = wrap(approval);
+        let namespace = Symbol::new(env, EVENT_NAMESPACE);
+        let name = Symbol::new(env, event.name());
+        let envelope = EventEnvelope {
+            version: EVENT_SCHEMA_VERSION,
+            event,
+        };
+        match scope {
+            OperatorScope::Stream(stream_id) => env
+                .events()
+                .publish((namespace, name, recipient, operator, stream_id), envelope),
+            OperatorScope::All => env
+                .events()
+                .publish((namespace, name, recipient, operator), envelope),
+        }
+    }
+
+    // ─── Statistics ──────────────────────────────────────────────────
+
+    /// Lifetime volume, fees and stream counts for `token`.
+    pub fn get_protocol_stats(env: Env, token: Address) -> ProtocolStats {
+        env.storage()
+            .persistent()
+            .get(&DataKey::ProtocolStats(token))
+            .unwrap_or_default()
+    }
+
+    fn update_stats<F>(env: &Env, token: &Address, update: F) -> Result<(), StreamError>
+    where
+        F: FnOnce(&mut ProtocolStats) -> Result<(), StreamError>,
+    {
+        let key = DataKey::ProtocolStats(token.clone());
+        let mut stats: ProtocolStats = env.storage().persistent().get(&key).unwrap_or_default();
+        update(&mut stats)?;
+        env.storage().persistent().set(&key, &stats);
+        Ok(())
+    }
+
+    // ─── Stream Index ────────────────────────────────────────────────
+
+    /// Stream IDs funded by `sender`, starting at position `cursor` in creation order.
+    /// At most `limit` IDs are returned, capped at `MAX_PAGE_SIZE`.
+    pub fn get_streams_by_sender(env: Env, sender: Address, cursor: u32, limit: u32) -> Vec<u64> {
+        Self::index_page(
+            &env,
+            DataKey::SenderStreamCount(sender.clone()),
+            |n| DataKey::SenderStream(sender.clone(), n),
+            cursor,
+            limit,
+        )
+    }
+
+    /// Stream IDs paying out to `recipient`, starting at position `cursor` in creation order.
+    /// At most `limit` IDs are returned, capped at `MAX_PAGE_SIZE`.
+    pub fn get_streams_by_recipient(
+        env: Env,
+        recipient: Address,
+        cursor: u32,
+        limit: u32,
+    ) -> Vec<u64> {
+        Self::index_page(
+            &env,
+            DataKey::RecipientStreamCount(recipient.clone()),
+            |n| DataKey::RecipientStream(recipient.clone(), n),
+            cursor,
+            limit,
+        )
+    }
+
+    /// Appends `stream_id` to an index. Each entry is its own ledger key, so the cost of
+    /// indexing doesn't grow with the number of streams an address already has.
+    fn index_stream<F>(
+        env: &Env,
+        count_key: DataKey,
+        entry_key: F,
+        stream_id: u64,
+    ) -> Result<(), StreamError>
+    where
+        F: Fn(u32) -> DataKey,
+    {
+        let storage = env.storage().persistent();
+        let count: u32 = storage.get(&count_key).unwrap_or(0);
+        storage.set(&entry_key(count), &stream_id);
+        let count = count.checked_add(1).ok_or(StreamError::ArithmeticOverflow)?;
+        storage.set(&count_key, &count);
+        Ok(())
+    }
+
+    fn index_page<F>(
+        env: &Env,
+        count_key: DataKey,
+        entry_key: F,
+        cursor: u32,
+        limit: u32,
+    ) -> Vec<u64>
+    where
+        F: Fn(u32) -> DataKey,
+    {
+        let storage = env.storage().persistent();
+        let count: u32 = storage.get(&count_key).unwrap_or(0);
+        let end = cursor.saturating_add(limit.min(MAX_PAGE_SIZE)).min(count);
+        let mut ids = Vec::new(env);
+        for n in cursor..end {
+            if let Some(stream_id) = storage.get(&entry_key(n)) {
+                ids.push_back(stream_id);
+            }
+        }
+        ids
+    }
 }
 
 mod test;
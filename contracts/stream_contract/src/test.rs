@@ -4,8 +4,8 @@ extern crate std;
 
 use super::*;
 use soroban_sdk::{
-    testutils::Address as _, testutils::Events, token, xdr, Address, Env, Symbol,
-    TryFromVal,
+    testutils::Address as _, testutils::Events, testutils::Ledger, token, xdr, Address, Env,
+    Symbol, TryFromVal, Val,
 };
 
 fn create_token_contract(env: &Env) -> (Address, Address) {
@@ -14,6 +14,53 @@ fn create_token_contract(env: &Env) -> (Address, Address) {
     (token.address(), admin)
 }
 
+/// Minimal token that burns 10% of every transfer, for exercising fee-on-transfer handling.
+#[contract]
+pub struct FeeOnTransferToken;
+
+#[contractimpl]
+impl FeeOnTransferToken {
+    pub fn mint(env: Env, to: Address, amount: i128) {
+        let balance = Self::balance(env.clone(), to.clone());
+        env.storage().persistent().set(&to, &(balance + amount));
+    }
+
+    pub fn balance(env: Env, id: Address) -> i128 {
+        env.storage().persistent().get(&id).unwrap_or(0)
+    }
+
+    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        let burned = amount / 10;
+        let from_balance = Self::balance(env.clone(), from.clone());
+        let to_balance = Self::balance(env.clone(), to.clone());
+        env.storage().persistent().set(&from, &(from_balance - amount));
+        env.storage().persistent().set(&to, &(to_balance + amount - burned));
+    }
+}
+
+/// Topics and payload of the most recent contract event published under `name`.
+fn last_raw_event(env: &Env, name: &str) -> Option<(Vec<Val>, Val)> {
+    let namespace = Some(Symbol::new(env, EVENT_NAMESPACE));
+    let name = Some(Symbol::new(env, name));
+    let topic = |topics: &Vec<Val>, i| topics.get(i).and_then(|t| Symbol::try_from_val(env, &t).ok());
+    env.events()
+        .all()
+        .iter()
+        .filter(|e| topic(&e.1, 0) == namespace && topic(&e.1, 1) == name)
+        .last()
+        .map(|e| (e.1, e.2))
+}
+
+/// Most recent contract event published under `name`, unwrapped from its envelope.
+fn last_event(env: &Env, name: &str) -> Option<ContractEvent> {
+    last_raw_event(env, name).map(|(_, data)| {
+        let envelope = EventEnvelope::try_from_val(env, &data).unwrap();
+        assert_eq!(envelope.version, EVENT_SCHEMA_VERSION);
+        envelope.event
+    })
+}
+
 #[test]
 fn test_create_stream_persists_state() {
     let env = Env::default();
@@ -29,7 +76,14 @@ fn test_create_stream_persists_state() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &500, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
     assert_eq!(stream_id, 1);
 
     let stream = client.get_stream(&stream_id).unwrap();
@@ -58,8 +112,22 @@ fn test_create_multiple_streams_increments_counter() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id1 = client.create_stream(&sender, &recipient1, &token_address, &500, &100);
-    let stream_id2 = client.create_stream(&sender, &recipient2, &token_address, &500, &100);
+    let stream_id1 = client.create_stream(
+        &sender,
+        &recipient1,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let stream_id2 = client.create_stream(
+        &sender,
+        &recipient2,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
 
     assert_eq!(stream_id1, 1);
     assert_eq!(stream_id2, 2);
@@ -81,7 +149,14 @@ fn test_withdraw_rejects_non_recipient() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &500, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
 
     let unauthorized_result = client.try_withdraw(&attacker, &stream_id);
     assert_eq!(unauthorized_result, Err(Ok(StreamError::Unauthorized)));
@@ -107,9 +182,17 @@ fn test_withdraw_authorized_recipient_receives_tokens() {
     let client = StreamContractClient::new(&env, &contract_id);
     let token_client = token::Client::new(&env, &token_address);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &500, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
     let recipient_balance_before = token_client.balance(&recipient);
 
+    env.ledger().with_mut(|li| li.timestamp = 100);
     let _ = client.withdraw(&recipient, &stream_id);
 
     let recipient_balance_after = token_client.balance(&recipient);
@@ -135,7 +218,14 @@ fn test_top_up_stream_success() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &10_000, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
 
     let top_up_result = client.try_top_up_stream(&sender, &stream_id, &5_000);
     assert!(top_up_result.is_ok());
@@ -159,7 +249,14 @@ fn test_top_up_stream_invalid_amount() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &10_000, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
 
     let negative_result = client.try_top_up_stream(&sender, &stream_id, &-100);
     assert_eq!(negative_result, Err(Ok(StreamError::InvalidAmount)));
@@ -199,7 +296,14 @@ fn test_top_up_stream_unauthorized() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &10_000, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
 
     let result = client.try_top_up_stream(&attacker, &stream_id, &1_000);
     assert_eq!(result, Err(Ok(StreamError::Unauthorized)));
@@ -220,7 +324,14 @@ fn test_top_up_stream_inactive() {
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &10_000, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
     client.cancel_stream(&sender, &stream_id);
 
     let result = client.try_top_up_stream(&sender, &stream_id, &1_000);
@@ -254,6 +365,16 @@ fn datakey_stream_serializes_deterministically_and_works_in_storage() {
         start_time: 1,
         last_update_time: 1,
         is_active: true,
+        rent_balance: 0,
+        last_bump_time: 1,
+        last_bump_ledger: 0,
+        fee_rate_bps: 0,
+        discounted_fee_rate_bps: 0,
+        fee_mode: FeeMode::OnDeposit,
+        fee_rounding: FeeRounding::Floor,
+        min_fee: 0,
+        referrer: None,
+        metadata: StreamMetadata::default(),
     };
 
     env.as_contract(&contract_id, || {
@@ -281,6 +402,11 @@ fn test_initialize_protocol_config() {
     assert_eq!(config.admin, admin);
     assert_eq!(config.treasury, treasury);
     assert_eq!(config.fee_rate_bps, fee_rate);
+    assert_eq!(config.fee_mode, FeeMode::OnDeposit);
+    assert_eq!(config.fee_rounding, FeeRounding::Floor);
+    assert_eq!(config.min_amount, 0);
+    assert_eq!(config.min_duration, 0);
+    assert_eq!(config.max_duration, 0);
 }
 
 #[test]
@@ -358,10 +484,17 @@ fn test_create_stream_with_fee() {
     // Create stream of 500
     // Expected fee: 500 * 200 / 10000 = 10
     // Expected net amount: 490
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &500, &100);
-
-    assert_eq!(token_client.balance(&treasury), 10);
-    assert_eq!(token_client.balance(&contract_id), 490);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+    assert_eq!(token_client.balance(&contract_id), 500);
 
     let stream = client.get_stream(&stream_id).unwrap();
     assert_eq!(stream.deposited_amount, 490);
@@ -384,20 +517,26 @@ fn test_top_up_with_fee() {
 
     let contract_id = env.register(StreamContract, ());
     let client = StreamContractClient::new(&env, &contract_id);
-    let token_client = token::Client::new(&env, &token_address);
 
     // Initialize fee: 1% (100 bps)
     client.initialize(&protocol_admin, &treasury, &100);
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &1000, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
     // Initial fee: 1000 * 0.01 = 10. Net: 990.
-    assert_eq!(token_client.balance(&treasury), 10);
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
 
     // Top up with 500
     // Top up fee: 500 * 0.01 = 5. Net: 495.
     client.top_up_stream(&sender, &stream_id, &500);
 
-    assert_eq!(token_client.balance(&treasury), 15);
+    assert_eq!(client.get_accrued_fees(&token_address), 15);
     
     let stream = client.get_stream(&stream_id).unwrap();
     assert_eq!(stream.deposited_amount, 990 + 495);
@@ -422,15 +561,2682 @@ fn test_fee_collected_event() {
 
     client.initialize(&protocol_admin, &treasury, &500); // 5%
 
-    let stream_id = client.create_stream(&sender, &recipient, &token_address, &1000, &100);
-
-    let events = env.events().all();
-    let fee_event = events.iter().find(|e| {
-        Symbol::try_from_val(&env, &e.1.get(0).unwrap()).unwrap() == Symbol::new(&env, "fee_collected")
-    }).unwrap();
-
-    let fee_collected: FeeCollectedEvent = FeeCollectedEvent::try_from_val(&env, &fee_event.2).unwrap();
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    let Some(ContractEvent::FeeCollected(fee_collected)) = last_event(&env, "fee_collected") else {
+        panic!("missing fee_collected event");
+    };
     assert_eq!(fee_collected.stream_id, stream_id);
-    assert_eq!(fee_collected.treasury, treasury);
+    assert_eq!(
+        fee_collected.recipients,
+        soroban_sdk::vec![&env, TreasurySplit { destination: treasury, weight_bps: 10_000 }]
+    );
     assert_eq!(fee_collected.fee_amount, 50);
+    assert_eq!(fee_collected.discount_bps, 0);
+    assert_eq!(fee_collected.fee_mode, FeeMode::OnDeposit);
+}
+
+#[test]
+fn test_set_emergency_mode_admin_only() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    client.initialize(&admin, &treasury, &0);
+
+    let result = client.try_set_emergency_mode(&attacker, &true);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+    assert!(!client.is_emergency_mode());
+
+    client.set_emergency_mode(&admin, &true);
+    assert!(client.is_emergency_mode());
+
+    client.set_emergency_mode(&admin, &false);
+    assert!(!client.is_emergency_mode());
+}
+
+#[test]
+fn test_create_stream_fails_in_emergency() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &treasury, &0);
+    client.set_emergency_mode(&admin, &true);
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::EmergencyStopEnabled)));
+}
+
+#[test]
+fn test_top_up_stream_fails_in_emergency() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &2_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &treasury, &0);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.set_emergency_mode(&admin, &true);
+
+    let result = client.try_top_up_stream(&sender, &stream_id, &500);
+    assert_eq!(result, Err(Ok(StreamError::EmergencyStopEnabled)));
+
+    let result = client.try_fund_push_tip(&sender, &stream_id, &100, &10);
+    assert_eq!(result, Err(Ok(StreamError::EmergencyStopEnabled)));
+}
+
+#[test]
+fn test_withdraw_allowed_in_emergency() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&admin, &treasury, &0);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.set_emergency_mode(&admin, &true);
+
+    env.ledger().with_mut(|li| li.timestamp = 50);
+    assert_eq!(client.withdraw(&recipient, &stream_id), 250);
+    assert_eq!(token_client.balance(&recipient), 250);
+}
+
+#[test]
+fn test_create_stream_holds_rent_budget() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: 40,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(token_client.balance(&contract_id), 540);
+    assert_eq!(token_client.balance(&sender), 460);
+
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.deposited_amount, 500);
+    assert_eq!(stream.rent_balance, 40);
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &100,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: -1,
+            ..Default::default()
+        },
+    );
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+}
+
+#[test]
+fn test_bump_stream_pays_keeper_per_interval() {
+    let env = Env::default();
+    env.mock_all_auths();
+    // Keep the token and contract entries alive while the ledger is moved weeks ahead.
+    env.ledger().with_mut(|li| {
+        li.min_persistent_entry_ttl = 10 * BUMP_INTERVAL_LEDGERS;
+        li.max_entry_ttl = 10 * BUMP_INTERVAL_LEDGERS;
+    });
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let keeper = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    // Four bump intervals long, with a budget of 400.
+    let duration = 4 * BUMP_INTERVAL_SECS;
+    let amount = duration as i128;
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &amount,
+        &duration,
+        &CreateStreamOptions {
+            rent_budget: 400,
+            ..Default::default()
+        },
+    );
+
+    let early = client.try_bump_stream(&keeper, &stream_id);
+    assert_eq!(early, Err(Ok(StreamError::BumpTooSoon)));
+
+    // One interval in, four intervals remain (including the current one): 400 / 4.
+    env.ledger().with_mut(|li| {
+        li.timestamp = BUMP_INTERVAL_SECS;
+        li.sequence_number = BUMP_INTERVAL_LEDGERS;
+    });
+    assert_eq!(client.bump_stream(&keeper, &stream_id), 100);
+    assert_eq!(token_client.balance(&keeper), 100);
+
+    // A second immediate bump would not extend the TTL, so it pays nothing.
+    let again = client.try_bump_stream(&keeper, &stream_id);
+    assert_eq!(again, Err(Ok(StreamError::BumpTooSoon)));
+    assert_eq!(token_client.balance(&keeper), 100);
+
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.rent_balance, 300);
+    assert_eq!(stream.last_bump_time, BUMP_INTERVAL_SECS);
+
+    // Past the end of the stream, the keeper collects whatever is left.
+    env.ledger().with_mut(|li| {
+        li.timestamp = 5 * BUMP_INTERVAL_SECS;
+        li.sequence_number = 5 * BUMP_INTERVAL_LEDGERS;
+    });
+    assert_eq!(client.bump_stream(&keeper, &stream_id), 300);
+    assert_eq!(client.get_stream(&stream_id).unwrap().rent_balance, 0);
+}
+
+#[test]
+fn test_bump_stream_waits_for_ttl_threshold() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| {
+        li.min_persistent_entry_ttl = 10 * BUMP_INTERVAL_LEDGERS;
+        li.max_entry_ttl = 10 * BUMP_INTERVAL_LEDGERS;
+    });
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let keeper = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let duration = 4 * BUMP_INTERVAL_SECS;
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &(duration as i128),
+        &duration,
+        &CreateStreamOptions {
+            rent_budget: 400,
+            ..Default::default()
+        },
+    );
+
+    // A week of wall-clock time has passed, but too few ledgers for the entry's TTL to have
+    // dropped to the threshold: the extension would be a no-op, so the keeper isn't paid.
+    env.ledger().with_mut(|li| {
+        li.timestamp = BUMP_INTERVAL_SECS;
+        li.sequence_number = BUMP_INTERVAL_LEDGERS - 1;
+    });
+    let result = client.try_bump_stream(&keeper, &stream_id);
+    assert_eq!(result, Err(Ok(StreamError::BumpTooSoon)));
+    assert_eq!(token_client.balance(&keeper), 0);
+
+    env.ledger().with_mut(|li| li.sequence_number = BUMP_INTERVAL_LEDGERS);
+    assert_eq!(client.bump_stream(&keeper, &stream_id), 100);
+    assert_eq!(token_client.balance(&keeper), 100);
+}
+
+#[test]
+fn test_unused_rent_refunded_on_cancel() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: 50,
+            ..Default::default()
+        },
+    );
+    assert_eq!(token_client.balance(&sender), 450);
+
+    client.cancel_stream(&sender, &stream_id);
+
+    assert_eq!(token_client.balance(&sender), 500);
+    assert_eq!(client.get_stream(&stream_id).unwrap().rent_balance, 0);
+}
+
+#[test]
+fn test_unused_rent_refunded_on_completion() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: 50,
+            ..Default::default()
+        },
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 100);
+    client.withdraw(&recipient, &stream_id);
+
+    assert_eq!(token_client.balance(&recipient), 500);
+    assert_eq!(token_client.balance(&sender), 500);
+    assert_eq!(token_client.balance(&contract_id), 0);
+}
+
+#[test]
+fn test_allowlist_rejects_unlisted_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &2_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &treasury, &0);
+    client.set_allowlist_enabled(&admin, &true);
+    assert!(client.is_allowlist_enabled());
+    assert!(!client.is_token_allowed(&token_address));
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::TokenNotAllowed)));
+
+    client.set_token_allowed(&admin, &token_address, &true);
+    assert!(client.is_token_allowed(&token_address));
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    // Delisting a token blocks further top-ups and tip funding of existing streams.
+    client.set_token_allowed(&admin, &token_address, &false);
+    let result = client.try_top_up_stream(&sender, &stream_id, &100);
+    assert_eq!(result, Err(Ok(StreamError::TokenNotAllowed)));
+    let result = client.try_fund_push_tip(&sender, &stream_id, &100, &10);
+    assert_eq!(result, Err(Ok(StreamError::TokenNotAllowed)));
+
+    // With the allowlist switched off, any token is accepted again.
+    client.set_allowlist_enabled(&admin, &false);
+    client.top_up_stream(&sender, &stream_id, &100);
+}
+
+#[test]
+fn test_allowlist_admin_only() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &treasury, &0);
+
+    let result = client.try_set_allowlist_enabled(&attacker, &true);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    let result = client.try_set_token_allowed(&attacker, &token_address, &true);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+    assert!(!client.is_token_allowed(&token_address));
+}
+
+#[test]
+fn test_token_fee_override() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (stable_token, _admin) = create_token_contract(&env);
+    let (volatile_token, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    token::StellarAssetClient::new(&env, &stable_token).mint(&sender, &1_000);
+    token::StellarAssetClient::new(&env, &volatile_token).mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100); // 1% globally
+    client.set_token_fee_rate(&protocol_admin, &stable_token, &20); // 0.2% for the stablecoin
+
+    assert_eq!(client.get_effective_fee(&stable_token), 20);
+    assert_eq!(client.get_effective_fee(&volatile_token), 100);
+
+    client.create_stream(
+        &sender,
+        &recipient,
+        &stable_token,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.create_stream(
+        &sender,
+        &recipient,
+        &volatile_token,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    assert_eq!(client.get_accrued_fees(&stable_token), 2);
+    assert_eq!(client.get_accrued_fees(&volatile_token), 10);
+
+    // Raising a token's effective rate, by override or by removing a lower one, is timelocked.
+    let result = client.try_set_token_fee_rate(&protocol_admin, &volatile_token, &500);
+    assert_eq!(result, Err(Ok(StreamError::FeeConfigTimelocked)));
+    let result = client.try_remove_token_fee_rate(&protocol_admin, &stable_token);
+    assert_eq!(result, Err(Ok(StreamError::FeeConfigTimelocked)));
+
+    let pending = client.queue_token_fee_rate(&protocol_admin, &stable_token, &None);
+    assert_eq!(
+        last_event(&env, "token_fee_rate_queued"),
+        Some(ContractEvent::TokenFeeRateQueued(pending.clone()))
+    );
+    assert_eq!(client.get_pending_token_fee_rate(&stable_token), Some(pending.clone()));
+
+    env.ledger().with_mut(|li| li.timestamp = pending.eta - 1);
+    let result = client.try_execute_token_fee_rate(&protocol_admin, &stable_token);
+    assert_eq!(result, Err(Ok(StreamError::TimelockNotExpired)));
+    assert_eq!(client.get_effective_fee(&stable_token), 20);
+
+    env.ledger().with_mut(|li| li.timestamp = pending.eta);
+    client.execute_token_fee_rate(&protocol_admin, &stable_token);
+    assert_eq!(
+        last_event(&env, "token_fee_rate_removed"),
+        Some(ContractEvent::TokenFeeRateRemoved(stable_token.clone()))
+    );
+    assert_eq!(client.get_effective_fee(&stable_token), 100);
+    assert_eq!(client.get_pending_token_fee_rate(&stable_token), None);
+
+    let pending = client.queue_token_fee_rate(&protocol_admin, &volatile_token, &Some(500));
+    client.cancel_token_fee_rate(&protocol_admin, &volatile_token);
+    assert_eq!(
+        last_event(&env, "token_fee_rate_cancelled"),
+        Some(ContractEvent::TokenFeeRateCancelled(pending))
+    );
+    let result = client.try_execute_token_fee_rate(&protocol_admin, &volatile_token);
+    assert_eq!(result, Err(Ok(StreamError::NoPendingTokenFeeRate)));
+}
+
+#[test]
+fn test_token_fee_override_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_effective_fee(&token_address), 0);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+
+    let result = client.try_set_token_fee_rate(&attacker, &token_address, &0);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    let result = client.try_set_token_fee_rate(&protocol_admin, &token_address, &1001);
+    assert_eq!(result, Err(Ok(StreamError::InvalidFeeRate)));
+
+    // A zero override fully waives the fee for that token.
+    client.set_token_fee_rate(&protocol_admin, &token_address, &0);
+    assert_eq!(client.get_effective_fee(&token_address), 0);
+}
+
+#[test]
+fn test_fee_discount_for_sender() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let partner = Address::generate(&env);
+    let treasury_flow = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&partner, &2_000);
+    stellar_asset.mint(&treasury_flow, &2_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &500); // 5%
+    client.set_fee_discount(&protocol_admin, &partner, &FeeDiscount::ReducedRate(100));
+    client.set_fee_discount(&protocol_admin, &treasury_flow, &FeeDiscount::Exempt);
+    assert_eq!(client.get_fee_discount(&partner), Some(FeeDiscount::ReducedRate(100)));
+
+    let partner_stream = client.create_stream(
+        &partner,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let Some(ContractEvent::FeeCollected(fee_collected)) = last_event(&env, "fee_collected") else {
+        panic!("missing fee_collected event");
+    };
+    assert_eq!(fee_collected.fee_amount, 10);
+    assert_eq!(fee_collected.discount_bps, 400);
+
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+    assert_eq!(client.get_stream(&partner_stream).unwrap().deposited_amount, 990);
+
+    let exempt_stream = client.create_stream(
+        &treasury_flow,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let Some(ContractEvent::FeeCollected(fee_collected)) = last_event(&env, "fee_collected") else {
+        panic!("missing fee_collected event");
+    };
+    assert_eq!(fee_collected.fee_amount, 0);
+    assert_eq!(fee_collected.discount_bps, 500);
+
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+    assert_eq!(client.get_stream(&exempt_stream).unwrap().deposited_amount, 1_000);
+
+    client.remove_fee_discount(&protocol_admin, &partner);
+    assert_eq!(client.get_fee_discount(&partner), None);
+    client.remove_fee_discount(&protocol_admin, &treasury_flow);
+
+    // Revoking a discount doesn't reprice existing streams: top-ups keep the discounted rate.
+    client.top_up_stream(&treasury_flow, &exempt_stream, &500);
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+    assert_eq!(client.get_stream(&exempt_stream).unwrap().deposited_amount, 1_500);
+
+    client.top_up_stream(&partner, &partner_stream, &1_000);
+    assert_eq!(client.get_accrued_fees(&token_address), 20);
+    assert_eq!(client.get_stream(&partner_stream).unwrap().deposited_amount, 1_980);
+
+    // New streams pay the full rate.
+    client.create_stream(
+        &treasury_flow,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.get_accrued_fees(&token_address), 45);
+}
+
+#[test]
+fn test_fee_discount_admin_only() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let account = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &500);
+
+    let result = client.try_set_fee_discount(&attacker, &account, &FeeDiscount::Exempt);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    let result =
+        client.try_set_fee_discount(&protocol_admin, &account, &FeeDiscount::ReducedRate(1001));
+    assert_eq!(result, Err(Ok(StreamError::InvalidFeeRate)));
+}
+
+#[test]
+fn test_top_up_uses_fee_rate_snapshot() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &3_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100); // 1%
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.get_stream(&stream_id).unwrap().fee_rate_bps, 100);
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+
+    // Fee hike to 5% does not touch the existing stream.
+    client.queue_fee_config(&protocol_admin, &treasury, &500);
+    env.ledger().with_mut(|li| li.timestamp = client.get_fee_config_delay());
+    client.execute_fee_config(&protocol_admin);
+    client.top_up_stream(&sender, &stream_id, &1_000);
+    assert_eq!(client.get_accrued_fees(&token_address), 20);
+
+    // The admin can't push the higher rate onto a live stream.
+    let result = client.try_apply_current_fee_rate(&protocol_admin, &stream_id);
+    assert_eq!(result, Err(Ok(StreamError::InvalidFeeRate)));
+
+    // A later cut below the snapshot can be passed on.
+    client.update_fee_config(&protocol_admin, &treasury, &50);
+    assert_eq!(client.apply_current_fee_rate(&protocol_admin, &stream_id), 50);
+    client.top_up_stream(&sender, &stream_id, &1_000);
+    assert_eq!(client.get_accrued_fees(&token_address), 25);
+
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.fee_rate_bps, 50);
+    assert_eq!(stream.deposited_amount, 990 + 990 + 995);
+}
+
+#[test]
+fn test_apply_current_fee_rate_admin_only() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    let result = client.try_apply_current_fee_rate(&attacker, &stream_id);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    let result = client.try_apply_current_fee_rate(&protocol_admin, &999);
+    assert_eq!(result, Err(Ok(StreamError::StreamNotFound)));
+}
+
+#[test]
+fn test_update_fee_config_requires_timelock_for_increase() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let new_treasury = Address::generate(&env);
+
+    client.initialize(&admin, &treasury, &500);
+
+    let result = client.try_update_fee_config(&admin, &treasury, &600);
+    assert_eq!(result, Err(Ok(StreamError::FeeConfigTimelocked)));
+
+    let result = client.try_update_fee_config(&admin, &new_treasury, &500);
+    assert_eq!(result, Err(Ok(StreamError::FeeConfigTimelocked)));
+
+    // Fee cuts still apply immediately.
+    client.update_fee_config(&admin, &treasury, &100);
+    assert_eq!(client.get_fee_config().unwrap().fee_rate_bps, 100);
+}
+
+#[test]
+fn test_queue_and_execute_fee_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let new_treasury = Address::generate(&env);
+
+    let delay = 3 * 24 * 60 * 60;
+    client.initialize(&admin, &treasury, &100);
+    client.set_fee_config_delay(&admin, &delay);
+    assert_eq!(client.get_fee_config_delay(), delay);
+
+    env.ledger().with_mut(|li| li.timestamp = 50);
+    let pending = client.queue_fee_config(&admin, &new_treasury, &300);
+    assert_eq!(pending.eta, 50 + delay);
+    assert_eq!(
+        last_event(&env, "fee_config_queued"),
+        Some(ContractEvent::FeeConfigQueued(pending.clone()))
+    );
+    assert_eq!(client.get_pending_fee_config(), Some(pending.clone()));
+
+    env.ledger().with_mut(|li| li.timestamp = 49 + delay);
+    let result = client.try_execute_fee_config(&admin);
+    assert_eq!(result, Err(Ok(StreamError::TimelockNotExpired)));
+    assert_eq!(client.get_fee_config().unwrap().fee_rate_bps, 100);
+
+    env.ledger().with_mut(|li| li.timestamp = 50 + delay);
+    client.execute_fee_config(&admin);
+    assert_eq!(
+        last_event(&env, "fee_config_executed"),
+        Some(ContractEvent::FeeConfigExecuted(pending.clone()))
+    );
+
+    let config = client.get_fee_config().unwrap();
+    assert_eq!(config.treasury, new_treasury);
+    assert_eq!(config.fee_rate_bps, 300);
+    assert_eq!(client.get_pending_fee_config(), None);
+
+    let result = client.try_execute_fee_config(&admin);
+    assert_eq!(result, Err(Ok(StreamError::NoPendingFeeConfig)));
+}
+
+#[test]
+fn test_cancel_fee_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    client.initialize(&admin, &treasury, &100);
+
+    let result = client.try_queue_fee_config(&attacker, &treasury, &1_000);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+    let result = client.try_queue_fee_config(&admin, &treasury, &1_001);
+    assert_eq!(result, Err(Ok(StreamError::InvalidFeeRate)));
+
+    let pending = client.queue_fee_config(&admin, &treasury, &1_000);
+
+    let result = client.try_cancel_fee_config(&attacker);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    client.cancel_fee_config(&admin);
+    assert_eq!(
+        last_event(&env, "fee_config_cancelled"),
+        Some(ContractEvent::FeeConfigCancelled(pending.clone()))
+    );
+    assert_eq!(client.get_pending_fee_config(), None);
+
+    env.ledger().with_mut(|li| li.timestamp = pending.eta);
+    let result = client.try_execute_fee_config(&admin);
+    assert_eq!(result, Err(Ok(StreamError::NoPendingFeeConfig)));
+    assert_eq!(client.get_fee_config().unwrap().fee_rate_bps, 100);
+
+    let result = client.try_set_fee_config_delay(&admin, &(31 * 24 * 60 * 60));
+    assert_eq!(result, Err(Ok(StreamError::InvalidDelay)));
+    let result = client.try_set_fee_config_delay(&admin, &0);
+    assert_eq!(result, Err(Ok(StreamError::InvalidDelay)));
+}
+
+#[test]
+fn test_fee_config_delay_reduction_waits_for_current_delay() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let new_treasury = Address::generate(&env);
+    let day = 24 * 60 * 60;
+
+    client.initialize(&admin, &treasury, &100);
+    assert_eq!(client.get_fee_config_delay(), 2 * day);
+
+    client.set_fee_config_delay(&admin, &day);
+    let pending = PendingFeeConfigDelay { delay: day, eta: 2 * day };
+    assert_eq!(
+        last_event(&env, "fee_config_delay_queued"),
+        Some(ContractEvent::FeeConfigDelayQueued(pending.clone()))
+    );
+    assert_eq!(client.get_pending_fee_config_delay(), Some(pending));
+
+    // Changes queued before the reduction lands still wait the full old delay.
+    assert_eq!(client.get_fee_config_delay(), 2 * day);
+    assert_eq!(client.queue_fee_config(&admin, &new_treasury, &300).eta, 2 * day);
+
+    env.ledger().with_mut(|li| li.timestamp = 2 * day);
+    assert_eq!(client.get_fee_config_delay(), day);
+    assert_eq!(client.queue_fee_config(&admin, &new_treasury, &300).eta, 3 * day);
+}
+
+#[test]
+fn test_claim_fees_pays_treasury() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &2_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.top_up_stream(&sender, &stream_id, &1_000);
+
+    assert_eq!(token_client.balance(&treasury), 0);
+    assert_eq!(client.get_accrued_fees(&token_address), 20);
+
+    assert_eq!(client.claim_fees(&token_address), 20);
+    assert_eq!(
+        env.auths()[0].0,
+        treasury,
+        "claiming fees must be authorized by the treasury"
+    );
+    let Some(ContractEvent::FeesClaimed(claimed)) = last_event(&env, "fees_claimed") else {
+        panic!("missing fees_claimed event");
+    };
+    assert_eq!(claimed.amount, 20);
+
+    assert_eq!(token_client.balance(&treasury), 20);
+    assert_eq!(client.get_accrued_fees(&token_address), 0);
+    assert_eq!(token_client.balance(&contract_id), 1_980);
+
+    let result = client.try_claim_fees(&token_address);
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+}
+
+#[test]
+fn test_fee_on_withdraw_mode() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &2_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &200); // 2%
+    client.set_fee_mode(&protocol_admin, &FeeMode::OnWithdraw);
+
+    // Nothing is charged up front.
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.top_up_stream(&sender, &stream_id, &1_000);
+    assert_eq!(client.get_accrued_fees(&token_address), 0);
+
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.fee_mode, FeeMode::OnWithdraw);
+    assert_eq!(stream.deposited_amount, 2_000);
+    assert_eq!(stream.rate_per_second, 10);
+
+    env.ledger().with_mut(|li| li.timestamp = 50);
+    assert_eq!(client.withdraw(&recipient, &stream_id), 490);
+
+    let Some(ContractEvent::FeeCollected(fee_collected)) = last_event(&env, "fee_collected") else {
+        panic!("missing fee_collected event");
+    };
+    assert_eq!(fee_collected.fee_amount, 10);
+    assert_eq!(fee_collected.fee_mode, FeeMode::OnWithdraw);
+
+    assert_eq!(token_client.balance(&recipient), 490);
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+    assert_eq!(client.get_stream(&stream_id).unwrap().withdrawn_amount, 500);
+}
+
+#[test]
+fn test_fee_mode_change_keeps_existing_streams() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &200);
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.get_accrued_fees(&token_address), 20);
+
+    let result = client.try_set_fee_mode(&attacker, &FeeMode::OnWithdraw);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    client.set_fee_mode(&protocol_admin, &FeeMode::OnWithdraw);
+    assert_eq!(client.get_fee_config().unwrap().fee_mode, FeeMode::OnWithdraw);
+
+    // Already charged on deposit, so the payout is not charged again.
+    env.ledger().with_mut(|li| li.timestamp = 200);
+    assert_eq!(client.withdraw(&recipient, &stream_id), 980);
+    assert_eq!(token_client.balance(&recipient), 980);
+    assert_eq!(client.get_accrued_fees(&token_address), 20);
+}
+
+#[test]
+fn test_referrer_receives_fee_share() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let referrer = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &2_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &200); // 2%
+    client.set_referral_share(&protocol_admin, &2_500); // referrer gets 25% of the fee
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions {
+            referrer: Some(referrer.clone()),
+            ..Default::default()
+        },
+    );
+    let Some(ContractEvent::FeeCollected(fee_collected)) = last_event(&env, "fee_collected") else {
+        panic!("missing fee_collected event");
+    };
+    assert_eq!(fee_collected.fee_amount, 20);
+    assert_eq!(fee_collected.referrer, Some(referrer.clone()));
+    assert_eq!(fee_collected.referrer_amount, 5);
+
+    assert_eq!(client.get_stream(&stream_id).unwrap().referrer, Some(referrer.clone()));
+    assert_eq!(client.get_accrued_fees(&token_address), 15);
+    assert_eq!(client.get_referral_fees(&referrer, &token_address), 5);
+
+    // Top-ups keep paying the referrer.
+    client.top_up_stream(&sender, &stream_id, &1_000);
+    assert_eq!(client.get_accrued_fees(&token_address), 30);
+    assert_eq!(client.get_referral_fees(&referrer, &token_address), 10);
+
+    assert_eq!(client.claim_referral_fees(&referrer, &token_address), 10);
+    assert_eq!(token_client.balance(&referrer), 10);
+    assert_eq!(client.get_referral_fees(&referrer, &token_address), 0);
+
+    let result = client.try_claim_referral_fees(&referrer, &token_address);
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+}
+
+#[test]
+fn test_referral_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &200);
+
+    let result = client.try_set_referral_share(&attacker, &5_000);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+    let result = client.try_set_referral_share(&protocol_admin, &10_001);
+    assert_eq!(result, Err(Ok(StreamError::InvalidShare)));
+    assert_eq!(client.get_referral_share(), 0);
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions {
+            referrer: Some(sender.clone()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(result, Err(Ok(StreamError::InvalidReferrer)));
+}
+
+#[test]
+fn test_distribute_fees_to_treasury_splits() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let operations = Address::generate(&env);
+    let grants = Address::generate(&env);
+    let insurance = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+
+    let splits = soroban_sdk::vec![
+        &env,
+        TreasurySplit { destination: operations.clone(), weight_bps: 5_000 },
+        TreasurySplit { destination: grants.clone(), weight_bps: 3_333 },
+        TreasurySplit { destination: insurance.clone(), weight_bps: 1_667 },
+    ];
+    let pending = client.queue_treasury_splits(&protocol_admin, &splits);
+    assert_eq!(client.get_pending_treasury_splits(), Some(pending.clone()));
+
+    // Redirecting fee revenue waits out the same delay as a treasury change.
+    env.ledger().with_mut(|li| li.timestamp = pending.eta - 1);
+    let result = client.try_execute_treasury_splits(&protocol_admin);
+    assert_eq!(result, Err(Ok(StreamError::TimelockNotExpired)));
+    assert!(client.get_treasury_splits().is_empty());
+
+    env.ledger().with_mut(|li| li.timestamp = pending.eta);
+    client.execute_treasury_splits(&protocol_admin);
+    assert_eq!(client.get_treasury_splits(), splits);
+    assert_eq!(client.get_pending_treasury_splits(), None);
+
+    client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let Some(ContractEvent::FeeCollected(fee_collected)) = last_event(&env, "fee_collected") else {
+        panic!("missing fee_collected event");
+    };
+    assert_eq!(fee_collected.recipients, splits);
+    assert_eq!(client.get_accrued_fees(&token_address), 100);
+
+    let result = client.try_claim_fees(&token_address);
+    assert_eq!(result, Err(Ok(StreamError::TreasurySplitsActive)));
+
+    assert_eq!(client.distribute_fees(&token_address), 100);
+    assert_eq!(token_client.balance(&operations), 50);
+    assert_eq!(token_client.balance(&grants), 33);
+    assert_eq!(token_client.balance(&insurance), 17);
+    assert_eq!(client.get_accrued_fees(&token_address), 0);
+
+    // Clearing the splits hands fees back to the single treasury.
+    let pending = client.queue_treasury_splits(&protocol_admin, &soroban_sdk::vec![&env]);
+    env.ledger().with_mut(|li| li.timestamp = pending.eta);
+    client.execute_treasury_splits(&protocol_admin);
+    let result = client.try_distribute_fees(&token_address);
+    assert_eq!(result, Err(Ok(StreamError::NoTreasurySplits)));
+}
+
+#[test]
+fn test_treasury_splits_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+
+    let valid = soroban_sdk::vec![
+        &env,
+        TreasurySplit { destination: a.clone(), weight_bps: 6_000 },
+        TreasurySplit { destination: b.clone(), weight_bps: 4_000 },
+    ];
+    let result = client.try_queue_treasury_splits(&attacker, &valid);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    let short = soroban_sdk::vec![
+        &env,
+        TreasurySplit { destination: a.clone(), weight_bps: 6_000 },
+        TreasurySplit { destination: b.clone(), weight_bps: 3_999 },
+    ];
+    let result = client.try_queue_treasury_splits(&protocol_admin, &short);
+    assert_eq!(result, Err(Ok(StreamError::InvalidShare)));
+
+    let zero_weight = soroban_sdk::vec![
+        &env,
+        TreasurySplit { destination: a.clone(), weight_bps: 10_000 },
+        TreasurySplit { destination: b.clone(), weight_bps: 0 },
+    ];
+    let result = client.try_queue_treasury_splits(&protocol_admin, &zero_weight);
+    assert_eq!(result, Err(Ok(StreamError::InvalidShare)));
+
+    assert_eq!(client.get_pending_treasury_splits(), None);
+
+    client.queue_treasury_splits(&protocol_admin, &valid);
+    let result = client.try_cancel_treasury_splits(&attacker);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+    client.cancel_treasury_splits(&protocol_admin);
+    let result = client.try_execute_treasury_splits(&protocol_admin);
+    assert_eq!(result, Err(Ok(StreamError::NoPendingTreasurySplits)));
+    assert!(client.get_treasury_splits().is_empty());
+}
+
+#[test]
+fn test_min_fee_and_rounding() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100); // 1%
+
+    // 1% of 150 is 1.5: floored by default, rounded up in Ceil mode.
+    assert_eq!(client.preview_fee(&token_address, &150), 1);
+    client.set_fee_rounding(&protocol_admin, &FeeRounding::Ceil);
+    assert_eq!(client.preview_fee(&token_address, &150), 2);
+    assert_eq!(client.preview_fee(&token_address, &10), 1);
+
+    client.set_fee_rounding(&protocol_admin, &FeeRounding::Floor);
+    assert_eq!(client.preview_fee(&token_address, &10), 0);
+
+    // A minimum fee stops tiny top-ups from dodging the fee entirely.
+    client.set_min_fee(&protocol_admin, &token_address, &3);
+    assert_eq!(client.get_min_fee(&token_address), 3);
+    assert_eq!(client.preview_fee(&token_address, &10), 3);
+    assert_eq!(client.preview_fee(&token_address, &1_000), 10);
+    assert_eq!(client.preview_fee(&token_address, &2), 2);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.top_up_stream(&sender, &stream_id, &10);
+    assert_eq!(client.get_accrued_fees(&token_address), 5 + 3);
+    assert_eq!(client.get_stream(&stream_id).unwrap().deposited_amount, 495 + 7);
+
+    client.set_min_fee(&protocol_admin, &token_address, &0);
+    assert_eq!(client.preview_fee(&token_address, &10), 0);
+
+    // Top-ups smaller than the stream's minimum fee would be swallowed whole.
+    let result = client.try_top_up_stream(&sender, &stream_id, &3);
+    assert_eq!(result, Err(Ok(StreamError::AmountTooLow)));
+}
+
+#[test]
+fn test_fee_settings_snapshotted_per_stream() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100); // 1%
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.get_accrued_fees(&token_address), 10);
+
+    // Raising the minimum or switching to Ceil later doesn't reprice the stream.
+    client.set_min_fee(&protocol_admin, &token_address, &50);
+    client.set_fee_rounding(&protocol_admin, &FeeRounding::Ceil);
+    client.top_up_stream(&sender, &stream_id, &150);
+    assert_eq!(client.get_accrued_fees(&token_address), 11);
+
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.min_fee, 0);
+    assert_eq!(stream.fee_rounding, FeeRounding::Floor);
+}
+
+#[test]
+fn test_min_fee_never_swallows_small_withdrawal() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100); // 1%
+    client.set_fee_mode(&protocol_admin, &FeeMode::OnWithdraw);
+    client.set_min_fee(&protocol_admin, &token_address, &20);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    // 10 accrued is below the minimum fee and isn't the final payout, so it can't be withdrawn
+    // yet; otherwise fees could be dodged by withdrawing in small chunks.
+    env.ledger().with_mut(|li| li.timestamp = 1);
+    let result = client.try_withdraw(&recipient, &stream_id);
+    assert_eq!(result, Err(Ok(StreamError::AmountTooLow)));
+
+    // Larger withdrawals pay the minimum.
+    env.ledger().with_mut(|li| li.timestamp = 51);
+    assert_eq!(client.withdraw(&recipient, &stream_id), 490);
+    assert_eq!(client.get_accrued_fees(&token_address), 20);
+
+    env.ledger().with_mut(|li| li.timestamp = 99);
+    assert_eq!(client.withdraw(&recipient, &stream_id), 460);
+    assert_eq!(client.get_accrued_fees(&token_address), 40);
+
+    // The final 10 is below the minimum too, but it's all that's left: only the 1% rate applies.
+    env.ledger().with_mut(|li| li.timestamp = 100);
+    assert_eq!(client.withdraw(&recipient, &stream_id), 10);
+    assert_eq!(client.get_accrued_fees(&token_address), 40);
+    assert_eq!(token_client.balance(&recipient), 960);
+}
+
+#[test]
+fn test_min_fee_skips_exempt_senders() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+    let attacker = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+
+    let result = client.try_set_min_fee(&attacker, &token_address, &5);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+    let result = client.try_set_min_fee(&protocol_admin, &token_address, &-1);
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+    let result = client.try_set_fee_rounding(&attacker, &FeeRounding::Ceil);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    client.set_min_fee(&protocol_admin, &token_address, &5);
+    client.set_fee_discount(&protocol_admin, &sender, &FeeDiscount::Exempt);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &100,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.get_accrued_fees(&token_address), 0);
+    assert_eq!(client.get_stream(&stream_id).unwrap().deposited_amount, 100);
+}
+
+#[test]
+fn test_create_stream_typed_validation_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &0,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &0,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::InvalidDuration)));
+
+    let result = client.try_create_stream(
+        &sender,
+        &sender,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::SelfStream)));
+
+    // 99 tokens over 100 seconds streams 0 per second and would lock the funds forever.
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &99,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::RateTooLow)));
+
+    // Failed creations move no funds and consume no stream IDs.
+    assert_eq!(token_client.balance(&sender), 1_000);
+    assert_eq!(
+        client.create_stream(
+            &sender,
+            &recipient,
+            &token_address,
+            &100,
+            &100,
+            &CreateStreamOptions::default()
+        ),
+        1
+    );
+}
+
+#[test]
+fn test_create_stream_rate_checked_after_fee() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &500); // 5%
+
+    // 100 gross covers 100 seconds, but the 95 left after fees does not.
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &100,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::RateTooLow)));
+    assert_eq!(client.get_accrued_fees(&token_address), 0);
+}
+
+#[test]
+fn test_create_stream_at_i128_max_without_fee() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &i128::MAX);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &i128::MAX,
+        &1,
+        &CreateStreamOptions::default(),
+    );
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.deposited_amount, i128::MAX);
+    assert_eq!(stream.rate_per_second, i128::MAX);
+
+    env.ledger().with_mut(|li| li.timestamp = u64::MAX);
+    assert_eq!(client.withdraw(&recipient, &stream_id), i128::MAX);
+}
+
+#[test]
+fn test_fee_overflow_returns_error() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &i128::MAX);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+
+    let amount = i128::MAX / 50;
+    let result = client.try_preview_fee(&token_address, &amount);
+    assert_eq!(result, Err(Ok(StreamError::ArithmeticOverflow)));
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &amount,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::ArithmeticOverflow)));
+
+    // Just below the boundary the fee still computes.
+    let amount = i128::MAX / 100;
+    assert_eq!(client.preview_fee(&token_address, &amount), amount / 100);
+}
+
+#[test]
+fn test_top_up_overflow_returns_error() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    // Push the stored deposit to the edge of i128 so the next top-up overflows.
+    env.as_contract(&contract_id, || {
+        let key = DataKey::Stream(stream_id);
+        let mut stream: Stream = env.storage().persistent().get(&key).unwrap();
+        stream.deposited_amount = i128::MAX - 10;
+        env.storage().persistent().set(&key, &stream);
+    });
+
+    let result = client.try_top_up_stream(&sender, &stream_id, &11);
+    assert_eq!(result, Err(Ok(StreamError::ArithmeticOverflow)));
+
+    client.top_up_stream(&sender, &stream_id, &10);
+    assert_eq!(client.get_stream(&stream_id).unwrap().deposited_amount, i128::MAX);
+}
+
+#[test]
+fn test_create_stream_rejects_contract_as_recipient() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let result = client.try_create_stream(
+        &sender,
+        &contract_id,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::InvalidRecipient)));
+}
+
+#[test]
+fn test_create_stream_enforces_stream_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &0);
+    client.set_stream_limits(&protocol_admin, &1_000, &60, &3_600);
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &999,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::AmountTooLow)));
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &59,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::DurationTooShort)));
+
+    let result = client.try_create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &5_000,
+        &3_601,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(result, Err(Ok(StreamError::DurationTooLong)));
+
+    client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &60,
+        &CreateStreamOptions::default(),
+    );
+    client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &3_600,
+        &3_600,
+        &CreateStreamOptions::default(),
+    );
+}
+
+#[test]
+fn test_set_stream_limits_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&protocol_admin, &treasury, &0);
+
+    let result = client.try_set_stream_limits(&attacker, &0, &0, &0);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+
+    let result = client.try_set_stream_limits(&protocol_admin, &-1, &0, &0);
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+
+    let result = client.try_set_stream_limits(&protocol_admin, &0, &100, &99);
+    assert_eq!(result, Err(Ok(StreamError::InvalidDuration)));
+
+    client.set_stream_limits(&protocol_admin, &10, &100, &0);
+    let config = client.get_fee_config().unwrap();
+    assert_eq!(config.min_amount, 10);
+    assert_eq!(config.min_duration, 100);
+    assert_eq!(config.max_duration, 0);
+}
+
+#[test]
+fn test_total_locked_tracks_stream_lifecycle() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &100); // 1%
+
+    let stream_a = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: 50,
+            ..Default::default()
+        },
+    );
+    let stream_b = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &2_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.get_total_locked(&token_address), 3_050);
+
+    client.top_up_stream(&sender, &stream_b, &1_000);
+    assert_eq!(client.get_total_locked(&token_address), 4_050);
+
+    env.ledger().with_mut(|li| li.timestamp = 10);
+    let paid = client.withdraw(&recipient, &stream_a);
+    assert_eq!(client.get_total_locked(&token_address), 4_050 - paid);
+
+    // Cancelling returns the rent budget to the sender.
+    client.cancel_stream(&sender, &stream_a);
+    assert_eq!(client.get_total_locked(&token_address), 4_000 - paid);
+
+    client.claim_fees(&token_address);
+    assert_eq!(client.get_total_locked(&token_address), 4_000 - paid - 40);
+
+    assert_eq!(token_client.balance(&contract_id), client.get_total_locked(&token_address));
+    client.check_invariants(&token_address);
+}
+
+#[test]
+fn test_check_invariants_detects_shortfall() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &500,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.check_invariants(&token_address);
+
+    // Simulate a loss: the contract now owes one more unit than it holds.
+    env.as_contract(&contract_id, || {
+        let key = DataKey::TotalLocked(token_address.clone());
+        env.storage().persistent().set(&key, &501_i128);
+    });
+
+    let result = client.try_check_invariants(&token_address);
+    assert_eq!(result, Err(Ok(StreamError::InvariantViolated)));
+}
+
+#[test]
+fn test_sweep_returns_only_surplus() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let careless_user = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000);
+    stellar_asset.mint(&careless_user, &300);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    let token_client = token::Client::new(&env, &token_address);
+
+    client.initialize(&protocol_admin, &treasury, &100);
+    client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    // Nothing to sweep while every token is owed to someone.
+    let result = client.try_sweep(&protocol_admin, &token_address, &careless_user);
+    assert_eq!(result, Err(Ok(StreamError::InvalidAmount)));
+
+    token_client.transfer(&careless_user, &contract_id, &300);
+
+    assert_eq!(client.sweep(&protocol_admin, &token_address, &careless_user), 300);
+    let Some(ContractEvent::TokensSwept(swept)) = last_event(&env, "tokens_swept") else {
+        panic!("missing tokens_swept event");
+    };
+    assert_eq!(swept.to, careless_user);
+    assert_eq!(swept.amount, 300);
+
+    assert_eq!(token_client.balance(&careless_user), 300);
+    assert_eq!(token_client.balance(&contract_id), 1_000);
+    client.check_invariants(&token_address);
+}
+
+#[test]
+fn test_sweep_admin_only() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let attacker = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let protocol_admin = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    token::StellarAssetClient::new(&env, &token_address).mint(&contract_id, &100);
+    client.initialize(&protocol_admin, &treasury, &100);
+
+    let result = client.try_sweep(&attacker, &token_address, &attacker);
+    assert_eq!(result, Err(Ok(StreamError::NotAdmin)));
+}
+
+#[test]
+fn test_fee_on_transfer_token_credits_received_amount() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let token_address = env.register(FeeOnTransferToken, ());
+    let token = FeeOnTransferTokenClient::new(&env, &token_address);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    token.mint(&sender, &20_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: 1_000,
+            ..Default::default()
+        },
+    );
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.deposited_amount, 9_000);
+    assert_eq!(stream.rate_per_second, 90);
+    assert_eq!(stream.rent_balance, 900);
+    assert_eq!(token.balance(&contract_id), 9_900);
+
+    client.top_up_stream(&sender, &stream_id, &5_000);
+    let stream = client.get_stream(&stream_id).unwrap();
+    assert_eq!(stream.deposited_amount, 13_500);
+    assert_eq!(client.get_total_locked(&token_address), 14_400);
+    client.check_invariants(&token_address);
+
+    // The full accrued amount can be paid out without the contract running short.
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    let paid = client.withdraw(&recipient, &stream_id);
+    assert_eq!(paid, 13_500);
+    assert_eq!(token.balance(&recipient), 12_150);
+    // Draining the stream refunds the rent, leaving nothing behind.
+    assert_eq!(token.balance(&contract_id), 0);
+    assert_eq!(client.get_total_locked(&token_address), 0);
+}
+
+#[test]
+fn test_streams_indexed_by_sender_and_recipient() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let first = client.create_stream(
+        &sender,
+        &alice,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let second = client.create_stream(
+        &sender,
+        &bob,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let third = client.create_stream(
+        &sender,
+        &alice,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    assert_eq!(
+        client.get_streams_by_sender(&sender, &0, &10),
+        Vec::from_array(&env, [first, second, third])
+    );
+    assert_eq!(
+        client.get_streams_by_recipient(&alice, &0, &10),
+        Vec::from_array(&env, [first, third])
+    );
+    assert_eq!(client.get_streams_by_recipient(&bob, &0, &10), Vec::from_array(&env, [second]));
+
+    // Pagination walks the list by position and clamps past the end.
+    assert_eq!(client.get_streams_by_sender(&sender, &1, &1), Vec::from_array(&env, [second]));
+    assert_eq!(client.get_streams_by_sender(&sender, &2, &10), Vec::from_array(&env, [third]));
+    assert_eq!(client.get_streams_by_sender(&sender, &5, &10), Vec::new(&env));
+    assert_eq!(client.get_streams_by_sender(&alice, &0, &10), Vec::new(&env));
+}
+
+#[test]
+fn test_list_and_batch_get_streams() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    assert_eq!(client.stream_count(), 0);
+    assert_eq!(client.list_streams(&1, &10).len(), 0);
+
+    let first = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let second = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &2_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let third = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &3_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    assert_eq!(client.stream_count(), 3);
+
+    let page = client.list_streams(&0, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap(), (first, client.get_stream(&first)));
+    assert_eq!(page.get(1).unwrap().0, second);
+
+    let rest = client.list_streams(&3, &10);
+    assert_eq!(rest.len(), 1);
+    assert_eq!(rest.get(0).unwrap().1.unwrap().deposited_amount, 3_000);
+
+    let batch = client.get_streams(&Vec::from_array(&env, [third, 99]));
+    assert_eq!(batch.get(0).unwrap(), (third, client.get_stream(&third)));
+    assert_eq!(batch.get(1).unwrap(), (99, None));
+}
+
+#[test]
+fn test_protocol_stats_track_volume_and_streams() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &50_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &treasury, &100);
+
+    assert_eq!(client.get_protocol_stats(&token_address), ProtocolStats::default());
+
+    let first = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &10_000,
+        &100,
+        &CreateStreamOptions {
+            rent_budget: 500,
+            ..Default::default()
+        },
+    );
+    let second = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &20_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    client.top_up_stream(&sender, &second, &1_000);
+
+    let stats = client.get_protocol_stats(&token_address);
+    assert_eq!(stats.total_deposited, 31_000);
+    assert_eq!(stats.total_fees, 310);
+    assert_eq!(stats.total_withdrawn, 0);
+    assert_eq!(stats.active_streams, 2);
+    assert_eq!(stats.total_streams, 2);
+
+    env.ledger().with_mut(|li| li.timestamp = 1_000);
+    client.withdraw(&recipient, &first);
+    client.cancel_stream(&sender, &second);
+
+    let stats = client.get_protocol_stats(&token_address);
+    assert_eq!(stats.total_withdrawn, 9_900);
+    assert_eq!(stats.active_streams, 0);
+    assert_eq!(stats.total_streams, 2);
+}
+
+#[test]
+fn test_admin_setters_publish_events() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let account = Address::generate(&env);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &treasury, &200);
+
+    client.set_fee_mode(&admin, &FeeMode::OnWithdraw);
+    assert_eq!(
+        last_event(&env, "fee_mode_updated"),
+        Some(ContractEvent::FeeModeUpdated(FeeMode::OnWithdraw))
+    );
+
+    client.set_fee_rounding(&admin, &FeeRounding::Ceil);
+    assert_eq!(
+        last_event(&env, "fee_rounding_updated"),
+        Some(ContractEvent::FeeRoundingUpdated(FeeRounding::Ceil))
+    );
+
+    client.set_stream_limits(&admin, &10, &60, &3_600);
+    assert_eq!(
+        last_event(&env, "stream_limits_updated"),
+        Some(ContractEvent::StreamLimitsUpdated(StreamLimitsUpdatedEvent {
+            min_amount: 10,
+            min_duration: 60,
+            max_duration: 3_600,
+        }))
+    );
+
+    client.set_token_fee_rate(&admin, &token_address, &50);
+    assert_eq!(
+        last_event(&env, "token_fee_rate_updated"),
+        Some(ContractEvent::TokenFeeRateUpdated(TokenFeeRateUpdatedEvent {
+            token: token_address.clone(),
+            fee_rate_bps: 50,
+        }))
+    );
+
+    // With the global rate no higher than the override, removing it applies immediately.
+    client.update_fee_config(&admin, &treasury, &50);
+    client.remove_token_fee_rate(&admin, &token_address);
+    assert_eq!(
+        last_event(&env, "token_fee_rate_removed"),
+        Some(ContractEvent::TokenFeeRateRemoved(token_address.clone()))
+    );
+
+    client.set_fee_discount(&admin, &account, &FeeDiscount::Exempt);
+    assert_eq!(
+        last_event(&env, "fee_discount_updated"),
+        Some(ContractEvent::FeeDiscountUpdated(FeeDiscountUpdatedEvent {
+            account: account.clone(),
+            discount: FeeDiscount::Exempt,
+        }))
+    );
+
+    client.remove_fee_discount(&admin, &account);
+    assert_eq!(
+        last_event(&env, "fee_discount_removed"),
+        Some(ContractEvent::FeeDiscountRemoved(account.clone()))
+    );
+
+    client.set_allowlist_enabled(&admin, &true);
+    assert_eq!(
+        last_event(&env, "allowlist_enabled_updated"),
+        Some(ContractEvent::AllowlistEnabledUpdated(true))
+    );
+
+    client.set_token_allowed(&admin, &token_address, &true);
+    assert_eq!(
+        last_event(&env, "token_allowed_updated"),
+        Some(ContractEvent::TokenAllowedUpdated(TokenAllowedUpdatedEvent {
+            token: token_address.clone(),
+            allowed: true,
+        }))
+    );
+
+    client.set_emergency_mode(&admin, &true);
+    assert_eq!(
+        last_event(&env, "emergency_mode_updated"),
+        Some(ContractEvent::EmergencyModeUpdated(true))
+    );
+
+    client.set_referral_share(&admin, &2_500);
+    assert_eq!(
+        last_event(&env, "referral_share_updated"),
+        Some(ContractEvent::ReferralShareUpdated(2_500))
+    );
+
+    client.set_min_fee(&admin, &token_address, &5);
+    assert_eq!(
+        last_event(&env, "min_fee_updated"),
+        Some(ContractEvent::MinFeeUpdated(MinFeeUpdatedEvent {
+            token: token_address,
+            min_fee: 5,
+        }))
+    );
+}
+
+#[test]
+fn test_events_use_versioned_schema_and_stream_topics() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    client.initialize(&admin, &treasury, &200);
+    assert_eq!(
+        last_event(&env, "initialized"),
+        Some(ContractEvent::Initialized(InitializedEvent {
+            admin: admin.clone(),
+            treasury: treasury.clone(),
+            fee_rate_bps: 200,
+        }))
+    );
+    let (topics, _) = last_raw_event(&env, "initialized").unwrap();
+    assert_eq!(topics.len(), 2);
+
+    client.update_fee_config(&admin, &treasury, &100);
+    assert_eq!(
+        last_event(&env, "fee_config_updated"),
+        Some(ContractEvent::FeeConfigUpdated(FeeConfigUpdatedEvent {
+            treasury: treasury.clone(),
+            old_fee_rate_bps: 200,
+            new_fee_rate_bps: 100,
+        }))
+    );
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let (topics, _) = last_raw_event(&env, "stream_created").unwrap();
+    assert_eq!(topics.len(), 5);
+    assert_eq!(u64::try_from_val(&env, &topics.get(2).unwrap()).unwrap(), stream_id);
+    assert_eq!(Address::try_from_val(&env, &topics.get(3).unwrap()).unwrap(), sender);
+    assert_eq!(Address::try_from_val(&env, &topics.get(4).unwrap()).unwrap(), recipient);
+    let Some(ContractEvent::StreamCreated(created)) = last_event(&env, "stream_created") else {
+        panic!("missing stream_created event");
+    };
+    assert_eq!(created.rate, 9);
+}
+
+#[test]
+fn test_runway_low_and_depleted_notifications() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+    client.initialize(&admin, &treasury, &0);
+
+    assert_eq!(client.get_runway_threshold(), 7 * 24 * 60 * 60);
+    client.set_runway_threshold(&admin, &50);
+    assert_eq!(client.get_runway_threshold(), 50);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 20);
+    client.withdraw(&recipient, &stream_id);
+    assert_eq!(last_event(&env, "stream_runway_low"), None);
+
+    env.ledger().with_mut(|li| li.timestamp = 60);
+    client.withdraw(&recipient, &stream_id);
+    assert_eq!(
+        last_event(&env, "stream_runway_low"),
+        Some(ContractEvent::StreamRunwayLow(StreamRunwayLowEvent {
+            stream_id,
+            remaining: 400,
+            runway_secs: 40,
+        }))
+    );
+
+    client.top_up_stream(&sender, &stream_id, &100);
+    assert_eq!(last_event(&env, "stream_runway_low"), None);
+
+    env.ledger().with_mut(|li| li.timestamp = 80);
+    assert!(!client.poke(&stream_id));
+    assert_eq!(
+        last_event(&env, "stream_runway_low"),
+        Some(ContractEvent::StreamRunwayLow(StreamRunwayLowEvent {
+            stream_id,
+            remaining: 300,
+            runway_secs: 30,
+        }))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 200);
+    assert!(client.poke(&stream_id));
+    assert_eq!(
+        last_event(&env, "stream_depleted"),
+        Some(ContractEvent::StreamDepleted(StreamDepletedEvent {
+            stream_id,
+            unclaimed: 500,
+            depleted_at: 110,
+        }))
+    );
+
+    // Later pokes still report depletion, but the event is only published once.
+    env.ledger().with_mut(|li| li.timestamp = 210);
+    assert!(client.poke(&stream_id));
+    assert_eq!(last_event(&env, "stream_depleted"), None);
+
+    client.withdraw(&recipient, &stream_id);
+    assert_eq!(client.try_poke(&stream_id), Err(Ok(StreamError::StreamInactive)));
+}
+
+#[test]
+fn test_stream_metadata_set_on_create_and_updated_by_sender() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let metadata = StreamMetadata {
+        memo: Some(String::from_str(&env, "Invoice 2024-017")),
+        category: Some(Symbol::new(&env, "payroll")),
+        reference: Some(BytesN::from_array(&env, &[7; 32])),
+    };
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions {
+            metadata: metadata.clone(),
+            ..Default::default()
+        },
+    );
+    let Some(ContractEvent::StreamCreated(created)) = last_event(&env, "stream_created") else {
+        panic!("missing stream_created event");
+    };
+    assert_eq!(created.metadata, metadata);
+    assert_eq!(client.get_stream(&stream_id).unwrap().metadata, metadata);
+
+    let updated = StreamMetadata {
+        memo: None,
+        category: Some(Symbol::new(&env, "vendor")),
+        reference: None,
+    };
+    let result = client.try_update_stream_metadata(&recipient, &stream_id, &updated);
+    assert_eq!(result, Err(Ok(StreamError::Unauthorized)));
+
+    client.update_stream_metadata(&sender, &stream_id, &updated);
+    assert_eq!(
+        last_event(&env, "stream_metadata_updated"),
+        Some(ContractEvent::StreamMetadataUpdated(StreamMetadataUpdatedEvent {
+            stream_id,
+            old_metadata: metadata,
+            new_metadata: updated.clone(),
+        }))
+    );
+    assert_eq!(client.get_stream(&stream_id).unwrap().metadata, updated);
+
+    let long_memo = StreamMetadata {
+        memo: Some(String::from_bytes(&env, &[b'x'; 257])),
+        category: None,
+        reference: None,
+    };
+    let result = client.try_update_stream_metadata(&sender, &stream_id, &long_memo);
+    assert_eq!(result, Err(Ok(StreamError::MemoTooLong)));
+}
+
+#[test]
+fn test_approved_operator_withdraws_to_recipient() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let payroll = Address::generate(&env);
+    let custodian = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let first = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let second = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let unrelated = client.create_stream(
+        &sender,
+        &custodian,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 10);
+    assert_eq!(client.try_withdraw(&payroll, &first), Err(Ok(StreamError::Unauthorized)));
+
+    // A recipient can only scope approvals to its own streams.
+    let result = client.try_approve_operator(&recipient, &payroll, &OperatorScope::Stream(unrelated));
+    assert_eq!(result, Err(Ok(StreamError::Unauthorized)));
+
+    client.approve_operator(&recipient, &payroll, &OperatorScope::Stream(first));
+    let (topics, _) = last_raw_event(&env, "operator_approved").unwrap();
+    assert_eq!(topics.len(), 5);
+    assert_eq!(Address::try_from_val(&env, &topics.get(2).unwrap()).unwrap(), recipient);
+    assert_eq!(Address::try_from_val(&env, &topics.get(3).unwrap()).unwrap(), payroll);
+    assert_eq!(u64::try_from_val(&env, &topics.get(4).unwrap()).unwrap(), first);
+
+    client.approve_operator(&recipient, &custodian, &OperatorScope::All);
+    let (topics, _) = last_raw_event(&env, "operator_approved").unwrap();
+    assert_eq!(topics.len(), 4);
+    assert_eq!(Address::try_from_val(&env, &topics.get(2).unwrap()).unwrap(), recipient);
+    assert_eq!(Address::try_from_val(&env, &topics.get(3).unwrap()).unwrap(), custodian);
+    assert_eq!(
+        last_event(&env, "operator_approved"),
+        Some(ContractEvent::OperatorApproved(OperatorApprovalEvent {
+            recipient: recipient.clone(),
+            operator: custodian.clone(),
+            scope: OperatorScope::All,
+        }))
+    );
+    assert_eq!(client.get_operator_approvals(&recipient).len(), 2);
+    assert!(client.is_operator(&recipient, &payroll, &first));
+    assert!(!client.is_operator(&recipient, &payroll, &second));
+    assert!(client.is_operator(&recipient, &custodian, &second));
+
+    assert_eq!(client.withdraw(&payroll, &first), 100);
+    assert_eq!(client.try_withdraw(&payroll, &second), Err(Ok(StreamError::Unauthorized)));
+    assert_eq!(client.withdraw(&custodian, &second), 100);
+    assert_eq!(token_client.balance(&recipient), 200);
+    assert_eq!(token_client.balance(&payroll), 0);
+    assert_eq!(token_client.balance(&custodian), 0);
+
+    client.revoke_operator(&recipient, &custodian, &OperatorScope::All);
+    let (topics, _) = last_raw_event(&env, "operator_revoked").unwrap();
+    assert_eq!(Address::try_from_val(&env, &topics.get(2).unwrap()).unwrap(), recipient);
+    assert_eq!(client.get_operator_approvals(&recipient).len(), 1);
+    env.ledger().with_mut(|li| li.timestamp = 20);
+    assert_eq!(client.try_withdraw(&custodian, &second), Err(Ok(StreamError::Unauthorized)));
+    let result = client.try_revoke_operator(&recipient, &custodian, &OperatorScope::All);
+    assert_eq!(result, Err(Ok(StreamError::OperatorNotApproved)));
+}
+
+#[test]
+fn test_push_withdraw_pays_recipient_and_keeper_tip() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let keeper = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &1_000_000);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let day = 24 * 60 * 60;
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &518_400,
+        &(3 * day),
+        &CreateStreamOptions::default(),
+    );
+    client.fund_push_tip(&sender, &stream_id, &25, &10);
+    assert_eq!(client.get_push_tip(&stream_id), Some(PushTip { balance: 25, per_call: 10 }));
+    assert_eq!(client.get_total_locked(&token_address), 518_425);
+
+    // Dust pushes are refused until a day has accrued.
+    env.ledger().with_mut(|li| li.timestamp = day - 1);
+    assert_eq!(client.try_push_withdraw(&keeper, &stream_id), Err(Ok(StreamError::PushTooSoon)));
+
+    env.ledger().with_mut(|li| li.timestamp = day);
+    assert_eq!(client.push_withdraw(&keeper, &stream_id), 10);
+    assert_eq!(
+        last_event(&env, "push_withdrawn"),
+        Some(ContractEvent::PushWithdrawn(PushWithdrawnEvent {
+            stream_id,
+            keeper: keeper.clone(),
+            amount: 172_800,
+            tip: 10,
+        }))
+    );
+    assert_eq!(token_client.balance(&recipient), 172_800);
+    assert_eq!(token_client.balance(&keeper), 10);
+
+    env.ledger().with_mut(|li| li.timestamp = 2 * day);
+    assert_eq!(client.push_withdraw(&keeper, &stream_id), 10);
+
+    // The final push closes the stream; the tip budget is spent down to what's left.
+    env.ledger().with_mut(|li| li.timestamp = 3 * day);
+    assert_eq!(client.push_withdraw(&keeper, &stream_id), 5);
+    assert_eq!(token_client.balance(&recipient), 518_400);
+    assert_eq!(token_client.balance(&keeper), 25);
+    assert!(!client.get_stream(&stream_id).unwrap().is_active);
+    assert_eq!(client.get_push_tip(&stream_id), None);
+    assert_eq!(client.get_total_locked(&token_address), 0);
+}
+
+#[test]
+fn test_unused_push_tip_refunded_on_cancel() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (token_address, _admin) = create_token_contract(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let stellar_asset = token::StellarAssetClient::new(&env, &token_address);
+    stellar_asset.mint(&sender, &10_000);
+    let token_client = token::Client::new(&env, &token_address);
+
+    let contract_id = env.register(StreamContract, ());
+    let client = StreamContractClient::new(&env, &contract_id);
+
+    let stream_id = client.create_stream(
+        &sender,
+        &recipient,
+        &token_address,
+        &1_000,
+        &100,
+        &CreateStreamOptions::default(),
+    );
+    let result = client.try_fund_push_tip(&recipient, &stream_id, &50, &5);
+    assert_eq!(result, Err(Ok(StreamError::Unauthorized)));
+
+    client.fund_push_tip(&sender, &stream_id, &50, &5);
+    assert_eq!(token_client.balance(&sender), 8_950);
+
+    client.cancel_stream(&sender, &stream_id);
+    assert_eq!(token_client.balance(&sender), 9_000);
+    assert_eq!(client.get_push_tip(&stream_id), None);
 }